@@ -0,0 +1,41 @@
+//! Random access to event offsets: [`EventList::time_of_event`] against
+//! walking the list with [`EventList::iter`].
+
+use chrono::Duration;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use jstris_replay_re::{EventList, Input};
+
+/// A 10k event list with events 150ms apart, i.e. a 25 minute game.
+fn events() -> EventList {
+    EventList::from_events(
+        (0..10_000).map(|i| (Input::ALL[i % 16], Duration::milliseconds(i as i64 * 150))),
+    )
+    .unwrap()
+}
+
+/// Arbitrary indices, like a scrubbing UI jumping around would make.
+fn indices(len: usize) -> impl Iterator<Item = usize> {
+    (0..1_000).map(move |i| (i * 7_919) % len)
+}
+
+fn time_of_event(c: &mut Criterion) {
+    let events = events();
+
+    c.bench_function("time_of_event", |b| {
+        b.iter(|| {
+            for idx in indices(events.len()) {
+                black_box(events.time_of_event(black_box(idx)));
+            }
+        })
+    });
+    c.bench_function("iter().nth", |b| {
+        b.iter(|| {
+            for idx in indices(events.len()) {
+                black_box(events.iter().nth(black_box(idx)));
+            }
+        })
+    });
+}
+
+criterion_group!(benches, time_of_event);
+criterion_main!(benches);
@@ -0,0 +1,167 @@
+//! A self-describing JSON format for decoded replays.
+//!
+//! Unlike the jstris format this stores the inputs as a list of named events
+//! with millisecond offsets instead of packed, base64 encoded, 12-bit
+//! timestamps. The packed form is regenerated when reading an archive back.
+
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{EventList, FromEventsError, Input, JstrisReplay, Metadata};
+
+pub const ARCHIVE_FORMAT: &str = "jstris-replay-re/archive";
+pub const ARCHIVE_VERSION: u32 = 1;
+
+#[derive(Debug, Error)]
+pub enum ArchiveError {
+    #[error("error encountered when decoding the archive JSON: {}", .0)]
+    JsonDecodeError(serde_json::Error),
+    #[error("expected an archive of format `{ARCHIVE_FORMAT}`, got `{format}`")]
+    UnknownFormat { format: String },
+    #[error("archive version {version} is not supported (expected {ARCHIVE_VERSION})")]
+    UnsupportedVersion { version: u32 },
+    #[error("the archived events can't be packed: {}", .0)]
+    EventsError(#[from] FromEventsError),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Archive {
+    format: String,
+    version: u32,
+    metadata: Metadata,
+    events: Vec<ArchivedEvent>,
+    /// Derived from the above; ignored when reading an archive.
+    stats: ArchiveStats,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ArchivedEvent {
+    #[serde(with = "input_name")]
+    input: Input,
+    /// Offset from the start of the game.
+    time_ms: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ArchiveStats {
+    duration_ms: i64,
+    num_events: usize,
+    num_pieces: usize,
+}
+
+// `Input`'s serde impls use the numeric codes; the archive uses the variant
+// names instead.
+mod input_name {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    use crate::Input;
+
+    pub fn serialize<S: Serializer>(input: &Input, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{input:?}"))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Input, D::Error> {
+        let name = String::deserialize(deserializer)?;
+
+        Input::ALL
+            .into_iter()
+            .find(|input| format!("{input:?}") == name)
+            .ok_or_else(|| D::Error::custom(format!("unknown input `{name}`")))
+    }
+}
+
+impl JstrisReplay {
+    /// Serializes the replay to the archive format described in the
+    /// [module docs](self).
+    pub fn to_archive_json(&self) -> Result<String, serde_json::Error> {
+        let events: Vec<_> = self
+            .data
+            .iter()
+            .map(|(input, offset)| ArchivedEvent {
+                input,
+                time_ms: offset.num_milliseconds(),
+            })
+            .collect();
+
+        let archive = Archive {
+            format: ARCHIVE_FORMAT.to_string(),
+            version: ARCHIVE_VERSION,
+            metadata: self.metadata.clone(),
+            stats: ArchiveStats {
+                duration_ms: self.time().num_milliseconds(),
+                num_events: events.len(),
+                num_pieces: self.placements().count(),
+            },
+            events,
+        };
+
+        serde_json::to_string(&archive)
+    }
+
+    /// The inverse of [`JstrisReplay::to_archive_json`].
+    pub fn from_archive_json(json: impl AsRef<str>) -> Result<JstrisReplay, ArchiveError> {
+        let archive: Archive =
+            serde_json::from_str(json.as_ref()).map_err(ArchiveError::JsonDecodeError)?;
+
+        if archive.format != ARCHIVE_FORMAT {
+            return Err(ArchiveError::UnknownFormat {
+                format: archive.format,
+            });
+        }
+        if archive.version != ARCHIVE_VERSION {
+            return Err(ArchiveError::UnsupportedVersion {
+                version: archive.version,
+            });
+        }
+
+        let data = EventList::from_events(
+            archive
+                .events
+                .iter()
+                .map(|ev| (ev.input, Duration::milliseconds(ev.time_ms))),
+        )?;
+
+        Ok(JstrisReplay {
+            metadata: archive.metadata,
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test_archive {
+    use super::*;
+    use crate::test_util;
+
+    #[test]
+    fn round_trip() {
+        let replay = test_util::sample();
+        let json = replay.to_archive_json().unwrap();
+
+        assert_eq!(JstrisReplay::from_archive_json(&json).unwrap(), replay);
+    }
+
+    #[test]
+    fn self_describing() {
+        let json = test_util::sample().to_archive_json().unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value["format"], ARCHIVE_FORMAT);
+        assert_eq!(value["version"], ARCHIVE_VERSION);
+        assert_eq!(value["events"][1]["input"], "HardDrop");
+        assert_eq!(value["events"][1]["time_ms"], 62);
+        assert_eq!(value["stats"]["duration_ms"], 15614);
+    }
+
+    #[test]
+    fn wrong_version() {
+        let json = test_util::sample().to_archive_json().unwrap();
+        let json = json.replace(r#""version":1"#, r#""version":2"#);
+
+        assert!(matches!(
+            JstrisReplay::from_archive_json(json),
+            Err(ArchiveError::UnsupportedVersion { version: 2 })
+        ));
+    }
+}
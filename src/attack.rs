@@ -0,0 +1,166 @@
+//! Garbage sent in versus games, worked out from the line clears found by
+//! [`JstrisReplay::simulate`].
+//!
+//! This uses jstris' default attack table:
+//!
+//! | clear              | lines sent |
+//! |--------------------|-----------:|
+//! | single             | 0          |
+//! | double             | 1          |
+//! | triple             | 2          |
+//! | tetris             | 4          |
+//! | T-spin mini single | 0          |
+//! | T-spin mini double | 1          |
+//! | T-spin single      | 2          |
+//! | T-spin double      | 4          |
+//! | T-spin triple      | 6          |
+//!
+//! plus:
+//!   - 1 for back-to-back: a tetris or a T-spin clear when the previous clear
+//!     was also one of those (pieces that don't clear lines don't break this)
+//!   - the combo bonus, [`COMBO_TABLE`], indexed by the number of clears in a
+//!     row before this one (pieces that don't clear lines do break this)
+//!   - 10 for a perfect clear
+//!
+//! Incoming garbage isn't simulated, so this is what was sent before any
+//! cancelling.
+
+use crate::{
+    sim::{Lock, TSpin},
+    JstrisReplay,
+};
+
+/// Extra lines sent for a combo; combos longer than this get the last entry.
+pub const COMBO_TABLE: [u32; 13] = [0, 0, 1, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+
+pub const BACK_TO_BACK_BONUS: u32 = 1;
+pub const PERFECT_CLEAR_BONUS: u32 = 10;
+
+/// The attack table from the [module docs](self), without any bonuses.
+const fn base_attack(lines: u8, t_spin: Option<TSpin>) -> u32 {
+    match (t_spin, lines) {
+        (None, 2) => 1,
+        (None, 3) => 2,
+        (None, 4) => 4,
+        (Some(TSpin::Mini), 2) => 1,
+        (Some(TSpin::Full), 1) => 2,
+        (Some(TSpin::Full), 2) => 4,
+        (Some(TSpin::Full), 3) => 6,
+        _ => 0,
+    }
+}
+
+#[derive(Debug, Default)]
+struct Attack {
+    // The number of clears in a row so far.
+    combo: usize,
+    back_to_back: bool,
+}
+
+impl Attack {
+    fn lock(&mut self, lock: &Lock) -> u32 {
+        if lock.lines_cleared == 0 {
+            self.combo = 0;
+            return 0;
+        }
+
+        let difficult = lock.lines_cleared == 4 || lock.t_spin.is_some();
+        let mut sent = base_attack(lock.lines_cleared, lock.t_spin);
+        if difficult && self.back_to_back {
+            sent += BACK_TO_BACK_BONUS;
+        }
+        sent += COMBO_TABLE[self.combo.min(COMBO_TABLE.len() - 1)];
+        if lock.perfect_clear {
+            sent += PERFECT_CLEAR_BONUS;
+        }
+
+        self.combo += 1;
+        self.back_to_back = difficult;
+        sent
+    }
+}
+
+fn lines_sent(locks: impl IntoIterator<Item = Lock>) -> u32 {
+    let mut attack = Attack::default();
+    locks.into_iter().map(|lock| attack.lock(&lock)).sum()
+}
+
+impl JstrisReplay {
+    /// The number of garbage lines the replay's clears would send in a versus
+    /// game; see the [module docs](self) for the attack table.
+    pub fn lines_sent(&self) -> u32 {
+        lines_sent(self.simulate())
+    }
+}
+
+#[cfg(test)]
+mod test_attack {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::{rng::Piece, test_util};
+
+    fn lock(lines_cleared: u8, t_spin: Option<TSpin>) -> Lock {
+        Lock {
+            piece: if t_spin.is_some() { Piece::T } else { Piece::I },
+            time: Duration::zero(),
+            lines_cleared,
+            t_spin,
+            perfect_clear: false,
+        }
+    }
+
+    #[test]
+    fn back_to_back_tetris() {
+        // 4, then 4 + 1 for back-to-back; the second is combo 1 which is 0.
+        assert_eq!(lines_sent([lock(4, None), lock(4, None)]), 9);
+
+        // The piece between them breaks the combo but not back-to-back.
+        assert_eq!(lines_sent([lock(4, None), lock(0, None), lock(4, None)]), 9);
+
+        // A single in between does break back-to-back.
+        assert_eq!(
+            lines_sent([
+                lock(4, None),
+                lock(0, None),
+                lock(1, None),
+                lock(0, None),
+                lock(4, None)
+            ]),
+            8
+        );
+    }
+
+    #[test]
+    fn t_spins() {
+        let tsd = lock(2, Some(TSpin::Full));
+        assert_eq!(lines_sent([tsd]), 4);
+        assert_eq!(lines_sent([tsd, lock(0, None), tsd]), 9);
+        assert_eq!(lines_sent([lock(2, Some(TSpin::Mini))]), 1);
+
+        // Back-to-back carries across tetrises and T-spins.
+        assert_eq!(lines_sent([lock(4, None), lock(0, None), tsd]), 9);
+    }
+
+    #[test]
+    fn combo() {
+        let singles = (0..14).map(|_| lock(1, None));
+        assert_eq!(lines_sent(singles), COMBO_TABLE.iter().sum::<u32>() + 5);
+    }
+
+    #[test]
+    fn perfect_clear() {
+        let pc = Lock {
+            perfect_clear: true,
+            ..lock(4, None)
+        };
+        assert_eq!(lines_sent([pc]), 14);
+    }
+
+    #[test]
+    fn sample() {
+        // Six tetrises in a row (4 + 5 * 5), two triples (2 each), a double
+        // (1), and 2 from a four-single combo.
+        assert_eq!(test_util::sample().lines_sent(), 36);
+    }
+}
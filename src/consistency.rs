@@ -0,0 +1,360 @@
+//! Checks for input sequences that a real player (using the jstris client)
+//! couldn't have produced.
+
+use chrono::{DateTime, Duration, Utc};
+use thiserror::Error;
+
+use crate::{Input, JstrisReplay, Metadata};
+
+/// An impossible input sequence; see [`JstrisReplay::consistency_warnings`].
+///
+/// `index` is the position of the offending event in the replay's
+/// [`EventList`](crate::EventList).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ConsistencyWarning {
+    /// A [`DasLeft`](Input::DasLeft)/[`DasRight`](Input::DasRight) whose
+    /// direction doesn't match the most recent
+    /// [`MoveLeft`](Input::MoveLeft)/[`MoveRight`](Input::MoveRight) (or that
+    /// comes before any move at all). DAS only charges while a key is held so
+    /// the key must have been pressed first.
+    DasWithoutPress { index: usize, time: Duration },
+    /// An [`ArrMove`](Input::ArrMove) without a DAS since the last move key
+    /// press; auto repeat only starts once DAS has charged.
+    ArrWithoutDas { index: usize, time: Duration },
+    /// A [`HardDrop`](Input::HardDrop) in the same millisecond as the one
+    /// before it, i.e. before the next piece could have spawned.
+    HardDropBeforeSpawn { index: usize, time: Duration },
+}
+
+impl JstrisReplay {
+    /// Flags input sequences the jstris client can't produce; useful for
+    /// catching tampered or badly synthesized replays.
+    ///
+    /// See [`ConsistencyWarning`] for the rules that are checked.
+    pub fn consistency_warnings(&self) -> Vec<ConsistencyWarning> {
+        use ConsistencyWarning::*;
+        use Input::*;
+
+        let mut warnings = vec![];
+
+        let mut last_press = None;
+        let mut das_charged = false;
+        let mut last_drop = None;
+        for (index, (input, time)) in self.data.iter().enumerate() {
+            match input {
+                MoveLeft | MoveRight => {
+                    last_press = Some(input);
+                    das_charged = false;
+                }
+                DasLeft | DasRight => {
+                    let pressed = if input == DasLeft {
+                        MoveLeft
+                    } else {
+                        MoveRight
+                    };
+                    if last_press != Some(pressed) {
+                        warnings.push(DasWithoutPress { index, time });
+                    }
+                    das_charged = true;
+                }
+                ArrMove if !das_charged => warnings.push(ArrWithoutDas { index, time }),
+                HardDrop => {
+                    if last_drop == Some(time) {
+                        warnings.push(HardDropBeforeSpawn { index, time });
+                    }
+                    last_drop = Some(time);
+                }
+                _ => {}
+            }
+        }
+
+        warnings
+    }
+}
+
+/// Bounds for [`JstrisReplay::plausible_for_mode`], per line the mode asks
+/// for. These are deliberately loose; they're meant to catch corrupt replays,
+/// not slow or unusual players.
+pub mod plausible {
+    /// Clearing a line from an empty board takes 2.5 pieces (10 cells, 4 per
+    /// piece), each of which needs a hard drop.
+    pub const MIN_INPUTS_PER_LINE: f64 = 2.5;
+    /// Fast sprints use 8 or so.
+    pub const MAX_INPUTS_PER_LINE: f64 = 50.;
+    /// 40 lines in 4s; the world record is over 10s.
+    pub const MIN_MILLIS_PER_LINE: i64 = 100;
+    /// 40 lines in 10 minutes.
+    pub const MAX_MILLIS_PER_LINE: i64 = 15_000;
+}
+
+impl JstrisReplay {
+    /// A rough check that the number of player inputs and the length of the
+    /// game are believable for the replay's sprint mode; see [`plausible`]
+    /// for the bounds.
+    ///
+    /// Always `true` for modes other than the line sprints.
+    pub fn plausible_for_mode(&self) -> bool {
+        use plausible::*;
+
+        let Some(lines) = self.metadata.game_mode.sprint_lines() else {
+            return true;
+        };
+
+        let inputs = self.data.iter().filter(|(i, _)| !i.is_system()).count() as f64;
+        let per_line_inputs = inputs / lines as f64;
+        let inputs_ok = (MIN_INPUTS_PER_LINE..=MAX_INPUTS_PER_LINE).contains(&per_line_inputs);
+
+        let min_time = Duration::milliseconds(MIN_MILLIS_PER_LINE * lines as i64);
+        let max_time = Duration::milliseconds(MAX_MILLIS_PER_LINE * lines as i64);
+        let time_ok = (min_time..=max_time).contains(&self.time());
+
+        inputs_ok && time_ok
+    }
+}
+
+/// How far past the end of the game the last event may be before
+/// [`JstrisReplay::validate`] complains; the end time and the event times are
+/// recorded separately so they don't always line up exactly.
+pub const EVENT_OVERRUN_TOLERANCE_MILLIS: i64 = 50;
+
+/// Something wrong with a replay; see [`JstrisReplay::validate`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Error)]
+pub enum ValidationIssue {
+    #[error("the game ends ({game_end}) before it starts ({game_start})")]
+    EndsBeforeStart {
+        game_start: DateTime<Utc>,
+        game_end: DateTime<Utc>,
+    },
+    /// The last event (not counting padding) is more than
+    /// [`EVENT_OVERRUN_TOLERANCE_MILLIS`] after the end of the game.
+    #[error(
+        "the last event is {}ms into the game, which only lasts {}ms",
+        .events.num_milliseconds(),
+        .game.num_milliseconds()
+    )]
+    EventsOverrun { events: Duration, game: Duration },
+    /// More than [`Metadata::MAX_HANDLING_MILLIS`].
+    #[error(
+        "a DAS of {das}ms is out of range (max {}ms)",
+        Metadata::MAX_HANDLING_MILLIS
+    )]
+    DasOutOfRange { das: u16 },
+    /// More than [`Metadata::MAX_HANDLING_MILLIS`].
+    #[error(
+        "an ARR of {arr}ms is out of range (max {}ms)",
+        Metadata::MAX_HANDLING_MILLIS
+    )]
+    ArrOutOfRange { arr: u16 },
+    /// No pieces were placed.
+    #[error("no pieces were placed")]
+    NoHardDrop,
+}
+
+impl JstrisReplay {
+    /// Checks that the replay is internally consistent: that the game ends
+    /// after it starts, that the events fit in the game, that the handling is
+    /// something jstris allows, and that at least one piece was placed.
+    ///
+    /// All the issues found are returned, not just the first. See
+    /// [`JstrisReplay::consistency_warnings`] for checking the inputs
+    /// themselves.
+    pub fn validate(&self) -> Result<(), Vec<ValidationIssue>> {
+        use ValidationIssue::*;
+
+        let mut issues = vec![];
+        let Metadata {
+            game_start,
+            game_end,
+            das,
+            arr,
+            ..
+        } = self.metadata;
+
+        if game_end < game_start {
+            issues.push(EndsBeforeStart {
+                game_start,
+                game_end,
+            });
+        }
+
+        let unpadded = self.data.len() - self.ends_with_padding() as usize;
+        let events = unpadded
+            .checked_sub(1)
+            .and_then(|last| self.data.time_of_event(last))
+            .unwrap_or_else(Duration::zero);
+        if events - self.time() > Duration::milliseconds(EVENT_OVERRUN_TOLERANCE_MILLIS) {
+            issues.push(EventsOverrun {
+                events,
+                game: self.time(),
+            });
+        }
+
+        match das {
+            Some(das) if das > Metadata::MAX_HANDLING_MILLIS => issues.push(DasOutOfRange { das }),
+            _ => {}
+        }
+        match arr {
+            Some(arr) if arr > Metadata::MAX_HANDLING_MILLIS => issues.push(ArrOutOfRange { arr }),
+            _ => {}
+        }
+
+        if !self.data.iter().any(|(input, _)| input == Input::HardDrop) {
+            issues.push(NoHardDrop);
+        }
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(issues)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_validate {
+    use super::*;
+    use crate::{test_util, Input::*};
+
+    #[test]
+    fn sample_is_valid() {
+        let sample = test_util::sample();
+        assert_eq!(sample.validate(), Ok(()));
+
+        // The padding after the end doesn't count:
+        assert!(sample.data.total_duration() > sample.time());
+    }
+
+    #[test]
+    fn everything_wrong() {
+        let ms = Duration::milliseconds;
+        let mut replay = test_util::with_events(&[(MoveLeft, 100), (MoveRight, 900)]);
+        replay.metadata.game_end = replay.metadata.game_start - ms(1);
+        replay.metadata.das = Some(5000);
+        replay.metadata.arr = Some(u16::MAX);
+
+        assert_eq!(
+            replay.validate(),
+            Err(vec![
+                ValidationIssue::EndsBeforeStart {
+                    game_start: replay.metadata.game_start,
+                    game_end: replay.metadata.game_end,
+                },
+                ValidationIssue::EventsOverrun {
+                    events: ms(900),
+                    game: ms(-1),
+                },
+                ValidationIssue::DasOutOfRange { das: 5000 },
+                ValidationIssue::ArrOutOfRange { arr: u16::MAX },
+                ValidationIssue::NoHardDrop,
+            ])
+        );
+    }
+
+    #[test]
+    fn overrun_tolerance() {
+        let ms = Duration::milliseconds;
+        let mut replay = test_util::with_events(&[(HardDrop, 1000), (HardDrop, 1050)]);
+
+        replay.metadata.game_end = replay.metadata.game_start + ms(1000);
+        assert_eq!(replay.validate(), Ok(()));
+
+        replay.metadata.game_end = replay.metadata.game_start + ms(999);
+        assert_eq!(
+            replay.validate(),
+            Err(vec![ValidationIssue::EventsOverrun {
+                events: ms(1050),
+                game: ms(999),
+            }])
+        );
+    }
+
+    #[test]
+    fn messages() {
+        let ms = Duration::milliseconds;
+        assert_eq!(
+            ValidationIssue::EventsOverrun {
+                events: ms(1050),
+                game: ms(999),
+            }
+            .to_string(),
+            "the last event is 1050ms into the game, which only lasts 999ms"
+        );
+        assert_eq!(
+            ValidationIssue::DasOutOfRange { das: 5000 }.to_string(),
+            "a DAS of 5000ms is out of range (max 4999ms)"
+        );
+    }
+}
+
+#[cfg(test)]
+mod test_consistency {
+    use super::*;
+    use crate::{test_util, Input::*};
+
+    #[test]
+    fn sample_is_consistent() {
+        assert_eq!(test_util::sample().consistency_warnings(), []);
+    }
+
+    #[test]
+    fn impossible_orderings() {
+        let ms = Duration::milliseconds;
+        let replay = test_util::with_events(&[
+            (DasRight, 100),
+            (ArrMove, 110),
+            (MoveLeft, 200),
+            (ArrMove, 210),
+            (DasLeft, 300),
+            (ArrMove, 310),
+            (HardDrop, 400),
+            (HardDrop, 400),
+        ]);
+
+        assert_eq!(
+            replay.consistency_warnings(),
+            [
+                ConsistencyWarning::DasWithoutPress {
+                    index: 0,
+                    time: ms(100)
+                },
+                ConsistencyWarning::ArrWithoutDas {
+                    index: 3,
+                    time: ms(210)
+                },
+                ConsistencyWarning::HardDropBeforeSpawn {
+                    index: 7,
+                    time: ms(400)
+                },
+            ]
+        );
+    }
+}
+
+#[cfg(test)]
+mod test_plausible {
+    use super::*;
+    use crate::{test_util, GameMode, Input::*};
+
+    #[test]
+    fn sample_is_plausible() {
+        assert!(test_util::sample().plausible_for_mode());
+    }
+
+    #[test]
+    fn one_event() {
+        // Same (40L) metadata as the sample, but with a single hard drop.
+        let replay = test_util::with_events(&[(HardDrop, 15_614)]);
+        assert!(!replay.plausible_for_mode());
+
+        let mut practice = replay.clone();
+        practice.metadata.game_mode = GameMode::Other(7);
+        assert!(practice.plausible_for_mode());
+    }
+
+    #[test]
+    fn too_long() {
+        let mut replay = test_util::sample();
+        replay.metadata.game_end += Duration::hours(2);
+        assert!(!replay.plausible_for_mode());
+    }
+}
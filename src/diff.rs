@@ -0,0 +1,84 @@
+//! Comparing two replays, e.g. two attempts on the same seed.
+
+use chrono::Duration;
+
+use crate::JstrisReplay;
+
+/// Where two replays' inputs stop matching; see [`JstrisReplay::diff`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ReplayDiff {
+    /// The number of leading events (input and time) the replays share.
+    pub common_prefix: usize,
+    /// The offset from the start of the game of the first event that
+    /// differs (the earlier of the two, if both replays have an event there);
+    /// `None` if the replays' inputs are identical.
+    pub divergence: Option<Duration>,
+}
+
+impl JstrisReplay {
+    /// Compares this replay's inputs to `other`'s.
+    ///
+    /// Only the events are compared; the metadata (including the seed) is
+    /// not.
+    pub fn diff(&self, other: &JstrisReplay) -> ReplayDiff {
+        let mut a = self.data.iter();
+        let mut b = other.data.iter();
+
+        let mut common_prefix = 0;
+        let divergence = loop {
+            match (a.next(), b.next()) {
+                (None, None) => break None,
+                (Some(x), Some(y)) if x == y => common_prefix += 1,
+                (Some((_, x)), Some((_, y))) => break Some(x.min(y)),
+                (Some((_, t)), None) | (None, Some((_, t))) => break Some(t),
+            }
+        };
+
+        ReplayDiff {
+            common_prefix,
+            divergence,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_diff {
+    use super::*;
+    use crate::{test_util, Input::*};
+
+    #[test]
+    fn identical() {
+        let sample = test_util::sample();
+        assert_eq!(
+            sample.diff(&sample),
+            ReplayDiff {
+                common_prefix: sample.data.len(),
+                divergence: None,
+            }
+        );
+    }
+
+    #[test]
+    fn diverging() {
+        let ms = Duration::milliseconds;
+        let a = test_util::with_events(&[(MoveLeft, 100), (HardDrop, 200), (HardDrop, 300)]);
+        let b = test_util::with_events(&[(MoveLeft, 100), (RotateLeft, 150), (HardDrop, 300)]);
+
+        let expected = ReplayDiff {
+            common_prefix: 1,
+            divergence: Some(ms(150)),
+        };
+        assert_eq!(a.diff(&b), expected);
+        assert_eq!(b.diff(&a), expected);
+
+        // One is a prefix of the other:
+        let c = test_util::with_events(&[(MoveLeft, 100)]);
+        assert_eq!(
+            a.diff(&c),
+            ReplayDiff {
+                common_prefix: 1,
+                divergence: Some(ms(200)),
+            }
+        );
+    }
+}
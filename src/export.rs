@@ -0,0 +1,461 @@
+//! Renderings of replays in formats meant for other tools.
+
+use std::{fmt::Write, io};
+
+use chrono::Duration;
+use serde_json::json;
+
+use crate::{Input, JstrisReplay};
+
+/// How long each input stays on screen in [`JstrisReplay::to_webvtt`].
+pub const WEBVTT_CUE_LENGTH_MILLIS: i64 = 250;
+
+/// `hh:mm:ss.ttt`
+fn webvtt_timestamp(d: Duration) -> String {
+    let ms = d.num_milliseconds();
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+impl JstrisReplay {
+    /// Renders the player's inputs as a WebVTT subtitle track, e.g. for
+    /// overlaying them on a recording of the game.
+    ///
+    /// Each input gets a cue showing its [default key
+    /// label](crate::Input::default_key_label), starting at the input's
+    /// offset from the start of the game and lasting
+    /// [`WEBVTT_CUE_LENGTH_MILLIS`]. Inputs without a label are skipped.
+    pub fn to_webvtt(&self) -> String {
+        let mut out = String::from("WEBVTT\n");
+        let cue_length = Duration::milliseconds(WEBVTT_CUE_LENGTH_MILLIS);
+
+        let cues = self
+            .iter_absolute()
+            .filter_map(|(input, at)| Some((input.default_key_label()?, at)));
+        for (label, at) in cues {
+            let start = at - self.metadata.game_start;
+            let _ = write!(
+                out,
+                "\n{} --> {}\n{label}\n",
+                webvtt_timestamp(start),
+                webvtt_timestamp(start + cue_length),
+            );
+        }
+
+        out
+    }
+}
+
+impl JstrisReplay {
+    /// Renders the replay in Chrome's [trace event format], for viewing in
+    /// `chrome://tracing` or [Perfetto](https://ui.perfetto.dev).
+    ///
+    /// The game is a single duration event with an instant event for each
+    /// input; player inputs and [system](crate::Input::is_system) events go
+    /// on separate tracks. Times are absolute (microseconds since the Unix
+    /// epoch).
+    ///
+    /// [trace event format]: https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU
+    pub fn to_chrome_trace(&self) -> String {
+        const PLAYER: u32 = 1;
+        const SYSTEM: u32 = 2;
+
+        let game_start = self.metadata.game_start.timestamp_micros();
+        let mut events = vec![
+            json!({
+                "name": "thread_name", "ph": "M", "pid": 1, "tid": PLAYER,
+                "args": { "name": "inputs" },
+            }),
+            json!({
+                "name": "thread_name", "ph": "M", "pid": 1, "tid": SYSTEM,
+                "args": { "name": "system events" },
+            }),
+            json!({
+                "name": format!("{:?}", self.metadata.game_mode),
+                "ph": "X", "pid": 1, "tid": PLAYER,
+                "ts": game_start,
+                "dur": self.time().num_microseconds(),
+            }),
+        ];
+
+        events.extend(self.iter_absolute().map(|(input, at)| {
+            json!({
+                "name": format!("{input:?}"),
+                "ph": "i", "s": "t", "pid": 1,
+                "tid": if input.is_system() { SYSTEM } else { PLAYER },
+                "ts": at.timestamp_micros(),
+            })
+        }));
+
+        json!({ "traceEvents": events, "displayTimeUnit": "ms" }).to_string()
+    }
+}
+
+impl JstrisReplay {
+    /// A plain text log of the player's keystrokes: one `<ms> <key>` line per
+    /// input (skipping [system](crate::Input::is_system) events), where `ms`
+    /// is the offset from the start of the game.
+    ///
+    /// Keys are [default key labels](crate::Input::default_key_label);
+    /// inputs without one (i.e. [`ArrMove`](crate::Input::ArrMove)) use the
+    /// input's [name](crate::Input::name) instead.
+    pub fn to_keylog(&self) -> String {
+        let mut out = String::new();
+
+        for (input, offset) in self.data.iter().filter(|(i, _)| !i.is_system()) {
+            let _ = match input.default_key_label() {
+                Some(label) => writeln!(out, "{} {label}", offset.num_milliseconds()),
+                None => writeln!(out, "{} {input}", offset.num_milliseconds()),
+            };
+        }
+
+        out
+    }
+}
+
+/// `m:ss.ttt`, with as many digits as needed for the minutes and a leading
+/// `-` for negative durations (e.g. the length of a game that ends before it
+/// starts).
+fn transcript_timestamp(d: Duration) -> String {
+    let sign = if d < Duration::zero() { "-" } else { "" };
+    let ms = d.num_milliseconds().unsigned_abs();
+    let (mins, secs, millis) = (ms / 60_000, ms / 1000 % 60, ms % 1000);
+    format!("{sign}{mins}:{secs:02}.{millis:03}")
+}
+
+impl JstrisReplay {
+    /// A plain text dump of the replay for reading: a header line with the
+    /// seed, mode, and length of the game, then a `m:ss.ttt  input` line for
+    /// every event (system events included) with its offset from the start
+    /// of the game.
+    pub fn to_transcript(&self) -> String {
+        let mut out = vec![];
+        self.write_transcript(&mut out)
+            .expect("writing to a Vec doesn't fail");
+        String::from_utf8(out).expect("the transcript is UTF-8")
+    }
+
+    /// [`JstrisReplay::to_transcript`], written to `w`.
+    pub fn write_transcript<W: io::Write>(&self, mut w: W) -> io::Result<()> {
+        let mode = match self.metadata.game_mode.sprint_lines() {
+            Some(lines) => format!("{lines}L sprint"),
+            None => format!("mode {}", self.metadata.game_mode.id()),
+        };
+        writeln!(
+            w,
+            "seed {}, {mode}, {}",
+            self.metadata.seed.as_str(),
+            transcript_timestamp(self.time())
+        )?;
+
+        for (input, offset) in self.data.iter() {
+            writeln!(w, "{}  {input}", transcript_timestamp(offset))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The width of the time axis in [`JstrisReplay::to_svg_timeline`], in
+/// pixels; the whole game is scaled to fit.
+pub const SVG_TIMELINE_WIDTH: u32 = 1000;
+/// The height of each input's row in [`JstrisReplay::to_svg_timeline`].
+pub const SVG_ROW_HEIGHT: u32 = 16;
+/// Room for the input names to the left of the time axis.
+const SVG_LABEL_WIDTH: u32 = 120;
+
+/// The tick color for each input in [`JstrisReplay::to_svg_timeline`];
+/// [system](Input::is_system) events are greys.
+const fn svg_color(input: Input) -> &'static str {
+    use Input::*;
+
+    match input {
+        MoveLeft => "#1f77b4",
+        MoveRight => "#ff7f0e",
+        DasLeft => "#aec7e8",
+        DasRight => "#ffbb78",
+        RotateLeft => "#2ca02c",
+        RotateRight => "#d62728",
+        Rotate180 => "#9467bd",
+        HardDrop => "#000000",
+        SoftDropBeginEnd => "#8c564b",
+        GravityStep => "#c7c7c7",
+        HoldBlock => "#e377c2",
+        GarbageAdd => "#7f7f7f",
+        SGarbageAdd => "#7f7f7f",
+        RedBarSet => "#a0a0a0",
+        ArrMove => "#17becf",
+        Aux => "#a0a0a0",
+    }
+}
+
+impl JstrisReplay {
+    /// Renders the replay's inputs as an SVG timeline: a row per kind of
+    /// input (that appears in the replay) with a colored tick at the time
+    /// of each input of that kind.
+    ///
+    /// Time runs left to right over [`SVG_TIMELINE_WIDTH`] pixels, from the
+    /// start of the game to its end (or the last input, if that's later).
+    /// Each tick has a `<title>` with the input and its offset in
+    /// milliseconds, which most viewers show on hover.
+    pub fn to_svg_timeline(&self) -> String {
+        let rows: Vec<Input> = Input::ALL
+            .into_iter()
+            .filter(|&input| self.data.iter().any(|(i, _)| i == input))
+            .collect();
+        let row = |input| rows.iter().position(|&i| i == input).unwrap() as u32;
+
+        let millis = self
+            .time()
+            .max(self.data.total_duration())
+            .num_milliseconds()
+            .max(1);
+        let x = |offset: Duration| {
+            SVG_LABEL_WIDTH as f64
+                + offset.num_milliseconds() as f64 * SVG_TIMELINE_WIDTH as f64 / millis as f64
+        };
+
+        let width = SVG_LABEL_WIDTH + SVG_TIMELINE_WIDTH;
+        let height = SVG_ROW_HEIGHT * rows.len() as u32;
+        let mut out = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n"
+        );
+
+        out.push_str("<g font-family=\"monospace\" font-size=\"10\">\n");
+        for (i, input) in rows.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "<text x=\"0\" y=\"{}\">{input}</text>",
+                SVG_ROW_HEIGHT * i as u32 + SVG_ROW_HEIGHT * 3 / 4,
+            );
+        }
+        out.push_str("</g>\n");
+
+        for (input, at) in self.iter_absolute() {
+            let offset = at - self.metadata.game_start;
+            let top = SVG_ROW_HEIGHT * row(input);
+            let _ = writeln!(
+                out,
+                "<line class=\"tick\" x1=\"{x:.1}\" y1=\"{}\" x2=\"{x:.1}\" y2=\"{}\" stroke=\"{}\"><title>{input} @ {}ms</title></line>",
+                top + 2,
+                top + SVG_ROW_HEIGHT - 2,
+                svg_color(input),
+                offset.num_milliseconds(),
+                x = x(offset),
+            );
+        }
+
+        out.push_str("</svg>\n");
+        out
+    }
+}
+
+#[cfg(test)]
+mod test_webvtt {
+    use super::*;
+    use crate::{test_util, Input::*};
+
+    #[test]
+    fn timestamps() {
+        let ms = Duration::milliseconds;
+
+        assert_eq!(webvtt_timestamp(ms(0)), "00:00:00.000");
+        assert_eq!(webvtt_timestamp(ms(15_614)), "00:00:15.614");
+        assert_eq!(webvtt_timestamp(ms(3_723_004)), "01:02:03.004");
+    }
+
+    #[test]
+    fn cue_per_input() {
+        let replay = test_util::with_events(&[
+            (MoveLeft, 100),
+            (GravityStep, 150),
+            (HardDrop, 4200),
+            (ArrMove, 4300),
+        ]);
+
+        assert_eq!(
+            replay.to_webvtt(),
+            "WEBVTT\n\
+             \n00:00:00.100 --> 00:00:00.350\nLeft\n\
+             \n00:00:04.200 --> 00:00:04.450\nSpace\n",
+        );
+    }
+
+    #[test]
+    fn well_formed() {
+        let replay = test_util::sample();
+        let vtt = replay.to_webvtt();
+
+        let mut blocks = vtt.split("\n\n");
+        assert_eq!(blocks.next(), Some("WEBVTT"));
+
+        let mut cues = 0;
+        for block in blocks {
+            let (timing, payload) = block.split_once('\n').unwrap();
+            let (start, end) = timing.split_once(" --> ").unwrap();
+            assert!(start < end, "{timing}");
+            assert!(!payload.trim().is_empty());
+            cues += 1;
+        }
+
+        let labelled = replay
+            .data
+            .iter()
+            .filter(|(input, _)| input.default_key_label().is_some())
+            .count();
+        assert_eq!(cues, labelled);
+    }
+}
+
+#[cfg(test)]
+mod test_chrome_trace {
+    use crate::test_util;
+
+    #[test]
+    fn trace_events() {
+        let replay = test_util::sample();
+        let trace: serde_json::Value = serde_json::from_str(&replay.to_chrome_trace()).unwrap();
+
+        let events = trace["traceEvents"].as_array().unwrap();
+        let instants: Vec<_> = events.iter().filter(|e| e["ph"] == "i").collect();
+        assert_eq!(instants.len(), replay.data.len());
+
+        let start = replay.metadata.game_start.timestamp_micros();
+        assert_eq!(instants[1]["name"], "HardDrop");
+        assert_eq!(instants[1]["ts"], start + 62_000);
+
+        let game = events.iter().find(|e| e["ph"] == "X").unwrap();
+        assert_eq!(game["ts"], start);
+        assert_eq!(game["dur"], 15_614_000);
+    }
+}
+
+#[cfg(test)]
+mod test_keylog {
+    use crate::{test_util, Input::*};
+
+    #[test]
+    fn line_per_input() {
+        let replay = test_util::with_events(&[
+            (MoveLeft, 100),
+            (GravityStep, 150),
+            (DasLeft, 250),
+            (ArrMove, 260),
+            (HardDrop, 4200),
+        ]);
+
+        assert_eq!(
+            replay.to_keylog(),
+            "100 Left\n250 Left\n260 arr-move\n4200 Space\n"
+        );
+    }
+
+    #[test]
+    fn sample() {
+        let replay = test_util::sample();
+        let keylog = replay.to_keylog();
+
+        let times: Vec<i64> = keylog
+            .lines()
+            .map(|line| line.split_once(' ').unwrap().0.parse().unwrap())
+            .collect();
+        let inputs = replay.data.iter().filter(|(i, _)| !i.is_system()).count();
+        assert_eq!(times.len(), inputs);
+        assert!(times.windows(2).all(|w| w[0] <= w[1]));
+    }
+}
+
+#[cfg(test)]
+mod test_transcript {
+    use super::*;
+    use crate::{test_util, GameMode, Input::*};
+
+    #[test]
+    fn lines() {
+        let replay = test_util::with_events(&[
+            (MoveLeft, 100),
+            (GravityStep, 1234),
+            (HardDrop, 4000),
+            (HardDrop, 8000),
+        ]);
+
+        assert_eq!(
+            replay.to_transcript(),
+            "seed c07yl8j, 40L sprint, 0:15.614\n\
+             0:00.100  move-left\n\
+             0:01.234  gravity-step\n\
+             0:04.000  hard-drop\n\
+             0:08.000  hard-drop\n"
+        );
+    }
+
+    #[test]
+    fn header() {
+        let mut replay = test_util::with_events(&[]);
+        replay.metadata.game_mode = GameMode::Other(7);
+        assert_eq!(replay.to_transcript(), "seed c07yl8j, mode 7, 0:15.614\n");
+
+        let ms = Duration::milliseconds;
+        assert_eq!(transcript_timestamp(ms(61_000)), "1:01.000");
+        assert_eq!(transcript_timestamp(ms(3_600_000)), "60:00.000");
+        assert_eq!(transcript_timestamp(ms(-1)), "-0:00.001");
+        assert_eq!(transcript_timestamp(ms(-61_000)), "-1:01.000");
+    }
+
+    #[test]
+    fn writer() {
+        let replay = test_util::sample();
+
+        let mut out = vec![];
+        replay.write_transcript(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), replay.to_transcript());
+        assert_eq!(
+            replay.to_transcript().lines().count(),
+            1 + replay.data.len()
+        );
+    }
+}
+
+#[cfg(test)]
+mod test_svg_timeline {
+    use crate::{test_util, Input::*};
+
+    #[test]
+    fn tick_per_input() {
+        let replay = test_util::sample();
+        let svg = replay.to_svg_timeline();
+        let doc = roxmltree::Document::parse(&svg).unwrap();
+
+        let ticks: Vec<_> = doc
+            .descendants()
+            .filter(|n| n.attribute("class") == Some("tick"))
+            .collect();
+        assert_eq!(ticks.len(), replay.data.len());
+
+        let hard_drop = ticks[1];
+        assert_eq!(
+            hard_drop.first_element_child().unwrap().text(),
+            Some("hard-drop @ 62ms")
+        );
+    }
+
+    #[test]
+    fn rows() {
+        let replay = test_util::with_events(&[(MoveLeft, 0), (HardDrop, 100), (MoveLeft, 200)]);
+        let svg = replay.to_svg_timeline();
+        let doc = roxmltree::Document::parse(&svg).unwrap();
+
+        let labels: Vec<_> = doc
+            .descendants()
+            .filter(|n| n.has_tag_name("text"))
+            .filter_map(|n| n.text())
+            .collect();
+        assert_eq!(labels, ["move-left", "hard-drop"]);
+        assert_eq!(doc.root_element().attribute("height"), Some("32"));
+    }
+}
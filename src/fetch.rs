@@ -0,0 +1,150 @@
+//! Fetching replays from jstris by id (behind the `reqwest` feature).
+
+use reqwest::StatusCode;
+use thiserror::Error;
+
+use crate::{decode_json, decode_uri_string, DecodeError, JstrisReplay};
+
+/// Where [`fetch_replay`] gets replays from; see [`fetch_replay_from`] for
+/// using a mirror instead.
+pub const DEFAULT_BASE_URL: &str = "https://jstris.jezevec10.com";
+
+#[derive(Debug, Error)]
+pub enum FetchError {
+    #[error("error encountered when requesting the replay: {}", .0)]
+    Network(#[from] reqwest::Error),
+    #[error("the server responded with {status}")]
+    Status { status: StatusCode },
+    #[error("the response isn't a replay: {}", .0)]
+    Decode(#[from] DecodeError),
+}
+
+/// Fetches the replay with the given id from jstris.
+pub async fn fetch_replay(id: u32) -> Result<JstrisReplay, FetchError> {
+    fetch_replay_from(DEFAULT_BASE_URL, id).await
+}
+
+/// [`fetch_replay`] from the jstris instance (or mirror) at `base_url`, e.g.
+/// `https://jstris.jezevec10.com` (without a trailing slash).
+pub async fn fetch_replay_from(base_url: &str, id: u32) -> Result<JstrisReplay, FetchError> {
+    let response = reqwest::get(format!("{base_url}/replay/data?id={id}&type=0")).await?;
+
+    let status = response.status();
+    if status != StatusCode::OK {
+        return Err(FetchError::Status { status });
+    }
+
+    Ok(parse_replay_response(&response.text().await?)?)
+}
+
+/// Decodes a response from the replay data endpoint; it usually responds with
+/// the replay as JSON, but can also give back the URI encoded form (possibly
+/// as a JSON string).
+pub fn parse_replay_response(body: &str) -> Result<JstrisReplay, DecodeError> {
+    let body = body.trim();
+
+    if body.starts_with('{') {
+        return decode_json(body);
+    }
+    if body.starts_with('"') {
+        let inner: String = serde_json::from_str(body).map_err(DecodeError::JsonDecodeError)?;
+        return parse_replay_response(&inner);
+    }
+
+    decode_uri_string(body)
+}
+
+#[cfg(test)]
+mod test_fetch {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+    use crate::{encode_uri_string, test_util};
+
+    /// Serves a single request with `status` and `body`; returns the base URL
+    /// to fetch from and the request that was received.
+    async fn serve_once(
+        status: &'static str,
+        body: String,
+    ) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            let mut request = vec![0; 4096];
+            let len = stream.read(&mut request).await.unwrap();
+            let request = String::from_utf8_lossy(&request[..len]).into_owned();
+
+            let response = format!(
+                "HTTP/1.1 {status}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+
+            request
+        });
+
+        (base_url, server)
+    }
+
+    #[test]
+    fn response_shapes() {
+        let replay = test_util::sample();
+        let encoded = encode_uri_string(&replay).unwrap();
+
+        let json = test_util::SAMPLE_JSON;
+        assert_eq!(parse_replay_response(json).unwrap(), replay);
+        assert_eq!(parse_replay_response(&format!("{json}\n")).unwrap(), replay);
+
+        assert_eq!(parse_replay_response(&encoded).unwrap(), replay);
+        let quoted = serde_json::to_string(&encoded).unwrap();
+        assert_eq!(parse_replay_response(&quoted).unwrap(), replay);
+
+        assert!(parse_replay_response("<html>not found</html>").is_err());
+    }
+
+    #[tokio::test]
+    async fn fetch() {
+        let (base_url, server) = serve_once("200 OK", test_util::SAMPLE_JSON.to_string()).await;
+
+        let replay = fetch_replay_from(&base_url, 70293904).await.unwrap();
+        assert_eq!(replay, test_util::sample());
+
+        let request = server.await.unwrap();
+        assert!(
+            request.starts_with("GET /replay/data?id=70293904&type=0 "),
+            "{request}"
+        );
+    }
+
+    #[tokio::test]
+    async fn errors() {
+        let (base_url, _) = serve_once("404 Not Found", "not found".to_string()).await;
+        assert!(matches!(
+            fetch_replay_from(&base_url, 1).await,
+            Err(FetchError::Status {
+                status: StatusCode::NOT_FOUND
+            })
+        ));
+
+        let (base_url, _) = serve_once("200 OK", "<html></html>".to_string()).await;
+        assert!(matches!(
+            fetch_replay_from(&base_url, 1).await,
+            Err(FetchError::Decode(_))
+        ));
+
+        // Nothing listening:
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        assert!(matches!(
+            fetch_replay_from(&base_url, 1).await,
+            Err(FetchError::Network(_))
+        ));
+    }
+}
@@ -0,0 +1,158 @@
+//! Mapping event times onto frames, e.g. for re-rendering a replay.
+//!
+//! jstris records inputs in milliseconds rather than frames, so events don't
+//! land exactly on frames. The frame rate is a parameter: jstris has run at
+//! 60fps, and the analysis in `main.rs` uses 30.
+
+use chrono::Duration;
+
+use crate::{EventList, Input};
+
+fn check_fps(fps: u32) {
+    assert!(fps > 0, "the frame rate must be non-zero");
+}
+
+/// The frame `offset` falls closest to (rounding halfway points up).
+fn nearest_frame(offset: Duration, fps: u32) -> i64 {
+    let micros = offset.num_microseconds().unwrap_or(i64::MAX);
+    (micros * fps as i64 * 2 + 1_000_000) / 2_000_000
+}
+
+/// When frame number `frame` starts, at `fps` frames per second.
+///
+/// # Panics
+///
+/// If `fps` is 0.
+pub fn frame_time(frame: u64, fps: u32) -> Duration {
+    check_fps(fps);
+    Duration::microseconds(frame as i64 * 1_000_000 / fps as i64)
+}
+
+/// How far event times are from the frames [`EventList::to_frames`] puts them
+/// on; see [`EventList::frame_drift`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FrameDriftReport {
+    pub fps: u32,
+    /// The largest distance between an event and its frame; at most half a
+    /// frame.
+    pub max_error: Duration,
+    /// The total error from instead rounding the time between each pair of
+    /// events to whole frames independently, which is how far a re-render
+    /// that works with frame delays would drift by the end of the game.
+    pub accumulated: Duration,
+}
+
+impl EventList {
+    /// Each input along with the index of the frame (at `fps` frames per
+    /// second) it happened closest to, counting from 0 at the start of the
+    /// game.
+    ///
+    /// Frames are picked from each event's offset from the start of the game
+    /// so the rounding doesn't accumulate.
+    ///
+    /// # Panics
+    ///
+    /// If `fps` is 0 (right away, not when the iterator is first advanced).
+    pub fn to_frames(&self, fps: u32) -> impl Iterator<Item = (Input, u64)> + '_ {
+        check_fps(fps);
+        self.iter()
+            .map(move |(input, offset)| (input, nearest_frame(offset, fps).max(0) as u64))
+    }
+
+    /// Summarizes the rounding error of mapping this list onto frames at
+    /// `fps` frames per second; see [`FrameDriftReport`].
+    ///
+    /// # Panics
+    ///
+    /// If `fps` is 0.
+    pub fn frame_drift(&self, fps: u32) -> FrameDriftReport {
+        check_fps(fps);
+        let mut max_error = Duration::zero();
+        let mut accumulated = Duration::zero();
+
+        let mut prev = Duration::zero();
+        for ((_, offset), (_, frame)) in self.iter().zip(self.to_frames(fps)) {
+            let error = offset - frame_time(frame, fps);
+            max_error = max_error.max(error).max(-error);
+
+            let delay = offset - prev;
+            let delay_frames = nearest_frame(delay, fps).max(0) as u64;
+            accumulated = accumulated + delay - frame_time(delay_frames, fps);
+            prev = offset;
+        }
+
+        FrameDriftReport {
+            fps,
+            max_error,
+            accumulated,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_frames {
+    use super::*;
+    use crate::{test_util, Input::*};
+
+    #[test]
+    fn nearest() {
+        let replay = test_util::with_events(&[
+            (MoveLeft, 0),
+            (MoveRight, 16),
+            (HardDrop, 25),
+            (HardDrop, 1000),
+        ]);
+
+        assert_eq!(
+            replay.data.to_frames(60).collect::<Vec<_>>(),
+            [(MoveLeft, 0), (MoveRight, 1), (HardDrop, 2), (HardDrop, 60)]
+        );
+        assert_eq!(
+            replay.data.to_frames(30).collect::<Vec<_>>(),
+            [(MoveLeft, 0), (MoveRight, 0), (HardDrop, 1), (HardDrop, 30)]
+        );
+    }
+
+    #[test]
+    fn drift() {
+        let ms = Duration::milliseconds;
+        // 20ms apart: 1.2 frames at 60fps, each rounded to 1 when taken on
+        // its own.
+        let events: Vec<_> = (1..=10).map(|i| (HardDrop, i * 20)).collect();
+        let replay = test_util::with_events(&events);
+
+        let report = replay.data.frame_drift(60);
+        assert_eq!(report.fps, 60);
+        assert!(report.max_error <= ms(8) + Duration::microseconds(334));
+        // 10 * (20ms - 16.666ms):
+        assert_eq!(report.accumulated, Duration::microseconds(33_340));
+
+        let sample = test_util::sample().data.frame_drift(60);
+        assert!(sample.max_error <= ms(9), "{sample:?}");
+    }
+
+    #[test]
+    fn frame_times() {
+        assert_eq!(frame_time(0, 60), Duration::zero());
+        assert_eq!(frame_time(60, 60), Duration::seconds(1));
+        assert_eq!(frame_time(1, 30), Duration::microseconds(33_333));
+    }
+
+    #[test]
+    #[should_panic(expected = "frame rate must be non-zero")]
+    fn zero_fps_frames() {
+        let _ = test_util::sample().data.to_frames(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "frame rate must be non-zero")]
+    fn zero_fps_drift() {
+        test_util::sample().data.frame_drift(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "frame rate must be non-zero")]
+    fn zero_fps_time() {
+        frame_time(1, 0);
+    }
+}
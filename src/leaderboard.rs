@@ -0,0 +1,372 @@
+//! Reading the jstris sprint leaderboards (behind the `leaderboard` feature).
+
+use std::{collections::HashSet, future::Future};
+
+use chrono::Duration;
+use soup::{NodeExt, QueryBuilderExt};
+use thiserror::Error;
+
+use crate::{fetch::DEFAULT_BASE_URL, GameMode};
+
+/// A row of a leaderboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LeaderboardEntry {
+    pub replay_id: u32,
+    pub time: Duration,
+    /// The entry's place, if the page shows one.
+    pub rank: Option<u32>,
+}
+
+#[derive(Debug, Error)]
+pub enum LeaderboardError {
+    #[error("game mode {id} doesn't have a sprint leaderboard")]
+    NoLeaderboard { id: u16 },
+    #[error("error encountered when requesting a leaderboard page: {}", .0)]
+    Network(#[from] reqwest::Error),
+}
+
+/// Parses a time the way the leaderboards display them: `s.sss`, `m:ss.sss`
+/// or `h:mm:ss.sss`.
+pub fn parse_time(text: &str) -> Option<Duration> {
+    let (rest, millis) = text.trim().split_once('.')?;
+    if millis.len() != 3 {
+        return None;
+    }
+    let millis: i64 = millis.parse().ok()?;
+
+    let mut secs = 0;
+    for (i, part) in rest.split(':').enumerate() {
+        // At most hours, minutes, and seconds:
+        if i > 2 {
+            return None;
+        }
+        secs = secs * 60 + part.parse::<i64>().ok()?;
+    }
+
+    Some(Duration::milliseconds(secs * 1000 + millis))
+}
+
+/// The inverse of [`parse_time`]; also used for the leaderboards' `page`
+/// parameter, which takes the time to start listing from.
+pub fn format_time(time: Duration) -> String {
+    let ms = time.num_milliseconds();
+    let (hours, mins, secs, millis) = (ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, ms % 1000);
+
+    match (hours, mins) {
+        (0, 0) => format!("{secs}.{millis:03}"),
+        (0, _) => format!("{mins}:{secs:02}.{millis:03}"),
+        _ => format!("{hours}:{mins:02}:{secs:02}.{millis:03}"),
+    }
+}
+
+/// The entries on a page of a sprint leaderboard, in the order they're
+/// listed (best first). Rows that don't look like entries are skipped.
+pub fn parse_leaderboard_page(page: &str) -> Vec<LeaderboardEntry> {
+    let soup = soup::Soup::new(page);
+    soup.tag("a")
+        .attr("target", "_blank")
+        .find_all()
+        .filter_map(|elem| {
+            let replay_id = elem
+                .get("href")?
+                .strip_prefix("https://jstris.jezevec10.com/replay/")?
+                .parse()
+                .ok()?;
+
+            let cells = elem
+                .parent()?
+                .parent()?
+                .tag("td")
+                .find_all()
+                .collect::<Vec<_>>();
+            let time = parse_time(&cells.get(2)?.tag("strong").find()?.text())?;
+            let rank = cells[0].text().trim().trim_end_matches('.').parse().ok();
+
+            Some(LeaderboardEntry {
+                replay_id,
+                time,
+                rank,
+            })
+        })
+        .collect()
+}
+
+/// The entries of a sprint leaderboard, best first, fetched a page at a time
+/// as they're needed.
+#[derive(Debug, Clone)]
+pub struct LeaderboardIter {
+    base_url: String,
+    lines: u32,
+    /// The rest of the current page, in reverse order (worst ... best).
+    remaining: Vec<LeaderboardEntry>,
+    /// The time the next page starts at.
+    next_page: Duration,
+    /// Pages start at the given time (rather than after it), so each page
+    /// can repeat the ties at the end of the one before it.
+    seen: HashSet<u32>,
+    pages_fetched: usize,
+    exhausted: bool,
+}
+
+impl LeaderboardIter {
+    /// Errors if `mode` isn't one of the line sprints.
+    pub fn new(mode: GameMode) -> Result<Self, LeaderboardError> {
+        let lines = mode
+            .sprint_lines()
+            .ok_or(LeaderboardError::NoLeaderboard { id: mode.id() })?;
+
+        Ok(Self {
+            base_url: DEFAULT_BASE_URL.to_string(),
+            lines,
+            remaining: Vec::with_capacity(200),
+            next_page: Duration::zero(),
+            seen: HashSet::new(),
+            pages_fetched: 0,
+            exhausted: false,
+        })
+    }
+
+    /// Starts listing from entries with a time of `time` (instead of from the
+    /// top of the leaderboard).
+    pub fn starting_at(mut self, time: Duration) -> Self {
+        self.next_page = time;
+        self
+    }
+
+    /// Uses the jstris instance (or mirror) at `base_url` instead of
+    /// [`DEFAULT_BASE_URL`].
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// How many pages of the leaderboard have been requested so far.
+    pub fn pages_fetched(&self) -> usize {
+        self.pages_fetched
+    }
+
+    /// The next entry, or `None` once a page has no new entries.
+    pub async fn next(&mut self) -> Result<Option<LeaderboardEntry>, LeaderboardError> {
+        self.next_with(&mut |url| async move { Ok(reqwest::get(url).await?.text().await?) })
+            .await
+    }
+
+    /// [`LeaderboardIter::next`] with `fetch` used to get each page (given
+    /// its URL).
+    async fn next_with<F, Fut>(
+        &mut self,
+        fetch: &mut F,
+    ) -> Result<Option<LeaderboardEntry>, LeaderboardError>
+    where
+        F: FnMut(String) -> Fut,
+        Fut: Future<Output = Result<String, LeaderboardError>>,
+    {
+        loop {
+            if let Some(entry) = self.remaining.pop() {
+                return Ok(Some(entry));
+            }
+            if self.exhausted {
+                return Ok(None);
+            }
+
+            // The top of the leaderboard has always been requested with
+            // `page=0.0`; keep doing that rather than `0.000`.
+            let page = if self.next_page.is_zero() {
+                "0.0".to_string()
+            } else {
+                format_time(self.next_page)
+            };
+            let url = format!("{}/sprint?lines={}L&page={page}", self.base_url, self.lines);
+            let page = fetch(url).await?;
+            self.pages_fetched += 1;
+
+            let new: Vec<_> = parse_leaderboard_page(&page)
+                .into_iter()
+                .filter(|entry| self.seen.insert(entry.replay_id))
+                .collect();
+            match new.last() {
+                Some(last) => self.next_page = last.time,
+                None => self.exhausted = true,
+            }
+            self.remaining.extend(new.into_iter().rev());
+        }
+    }
+}
+
+/// The entries on the first `max_pages` pages of the sprint leaderboard for
+/// `mode`, best first.
+///
+/// Stops early if a page has no entries that haven't been seen already.
+pub async fn collect_leaderboard(
+    mode: GameMode,
+    max_pages: usize,
+) -> Result<Vec<LeaderboardEntry>, LeaderboardError> {
+    collect_leaderboard_with(
+        LeaderboardIter::new(mode)?,
+        max_pages,
+        &mut |url| async move { Ok(reqwest::get(url).await?.text().await?) },
+    )
+    .await
+}
+
+/// The `(time, replay id)` of the entries on the first `max_pages` pages of
+/// the sprint leaderboard for `mode`, best first; [`collect_leaderboard`]
+/// with the times [formatted](format_time) the way the leaderboard shows
+/// them.
+pub async fn collect_leaderboard_ids(
+    mode: GameMode,
+    max_pages: usize,
+) -> Result<Vec<(String, u32)>, LeaderboardError> {
+    Ok(into_ids(collect_leaderboard(mode, max_pages).await?))
+}
+
+fn into_ids(entries: Vec<LeaderboardEntry>) -> Vec<(String, u32)> {
+    entries
+        .into_iter()
+        .map(|entry| (format_time(entry.time), entry.replay_id))
+        .collect()
+}
+
+async fn collect_leaderboard_with<F, Fut>(
+    mut iter: LeaderboardIter,
+    max_pages: usize,
+    fetch: &mut F,
+) -> Result<Vec<LeaderboardEntry>, LeaderboardError>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<String, LeaderboardError>>,
+{
+    let mut entries = vec![];
+    while !iter.remaining.is_empty() || iter.pages_fetched < max_pages {
+        match iter.next_with(fetch).await? {
+            Some(entry) => entries.push(entry),
+            None => break,
+        }
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod test_leaderboard {
+    use super::*;
+
+    const PAGES: [&str; 2] = [
+        include_str!("../tests/fixtures/leaderboard-40l-1.html"),
+        include_str!("../tests/fixtures/leaderboard-40l-2.html"),
+    ];
+
+    fn entry(rank: u32, time: i64, replay_id: u32) -> LeaderboardEntry {
+        LeaderboardEntry {
+            replay_id,
+            time: Duration::milliseconds(time),
+            rank: Some(rank),
+        }
+    }
+
+    #[test]
+    fn times() {
+        let ms = Duration::milliseconds;
+        for (text, time) in [
+            ("14.512", ms(14_512)),
+            ("0.000", ms(0)),
+            ("1:02.345", ms(62_345)),
+            ("10:00.000", ms(600_000)),
+            ("1:00:01.001", ms(3_601_001)),
+        ] {
+            assert_eq!(parse_time(text), Some(time), "{text}");
+            assert_eq!(format_time(time), text);
+        }
+
+        for text in ["", "14", "14.5", "a.123", "1:2:3:4.000", "14.5123"] {
+            assert_eq!(parse_time(text), None, "{text}");
+        }
+    }
+
+    #[test]
+    fn page() {
+        assert_eq!(
+            parse_leaderboard_page(PAGES[0]),
+            [entry(1, 14_512, 70000001), entry(2, 15_003, 70000002)]
+        );
+        assert_eq!(parse_leaderboard_page("<html></html>"), []);
+    }
+
+    #[tokio::test]
+    async fn pages() {
+        let mut urls = vec![];
+        let mut fetch = |url| {
+            let page = PAGES.get(urls.len()).copied().unwrap_or("<html></html>");
+            urls.push(url);
+            async move { Ok(page.to_string()) }
+        };
+
+        let iter = LeaderboardIter::new(GameMode::_40Line).unwrap();
+        let entries = collect_leaderboard_with(iter, 5, &mut fetch).await.unwrap();
+
+        // The second page starts with the last entry of the first.
+        assert_eq!(
+            entries,
+            [
+                entry(1, 14_512, 70000001),
+                entry(2, 15_003, 70000002),
+                entry(3, 15_240, 70000003),
+                entry(4, 15_871, 70000004),
+            ]
+        );
+        // The third page was empty.
+        assert_eq!(
+            urls,
+            [
+                "https://jstris.jezevec10.com/sprint?lines=40L&page=0.0",
+                "https://jstris.jezevec10.com/sprint?lines=40L&page=15.003",
+                "https://jstris.jezevec10.com/sprint?lines=40L&page=15.871",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn ids() {
+        let mut fetched = 0;
+        let mut fetch = |_| {
+            let page = PAGES[fetched];
+            fetched += 1;
+            async move { Ok(page.to_string()) }
+        };
+
+        let iter = LeaderboardIter::new(GameMode::_40Line).unwrap();
+        let entries = collect_leaderboard_with(iter, 2, &mut fetch).await.unwrap();
+        assert_eq!(
+            into_ids(entries),
+            [
+                ("14.512".to_string(), 70000001),
+                ("15.003".to_string(), 70000002),
+                ("15.240".to_string(), 70000003),
+                ("15.871".to_string(), 70000004),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn configured() {
+        let mut urls = vec![];
+        let mut fetch = |url| {
+            urls.push(url);
+            async { Ok(PAGES[1].to_string()) }
+        };
+
+        let iter = LeaderboardIter::new(GameMode::_100Line)
+            .unwrap()
+            .starting_at(Duration::milliseconds(75_000))
+            .with_base_url("http://mirror");
+        let entries = collect_leaderboard_with(iter, 1, &mut fetch).await.unwrap();
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(urls, ["http://mirror/sprint?lines=100L&page=1:15.000"]);
+
+        assert!(matches!(
+            LeaderboardIter::new(GameMode::Other(7)),
+            Err(LeaderboardError::NoLeaderboard { id: 7 })
+        ));
+    }
+}
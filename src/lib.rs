@@ -1,23 +1,63 @@
 use std::{
-    fmt::{self, Debug, Display},
+    fmt::{self, Debug, Display, Write as _},
     hash::Hash,
+    io::{self, Write},
+    ops::{DerefMut, Range},
+    sync::OnceLock,
 };
 
-use chrono::{serde::ts_milliseconds, DateTime, Duration, Utc};
-use derive_more::{Deref, DerefMut};
-use serde::{de::Error, Deserialize, Serialize, ser::SerializeStruct};
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use derive_more::Deref;
+use serde::{de::Error, ser::SerializeStruct, Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
-use serde_with::{base64::Base64, serde_as, ser::SerializeAsWrap};
+use serde_with::{base64::Base64, ser::SerializeAsWrap, serde_as};
 use thiserror::Error;
 
+pub mod archive;
+pub mod attack;
+pub mod consistency;
+pub mod diff;
+pub mod export;
+#[cfg(feature = "reqwest")]
+pub mod fetch;
+pub mod frames;
+#[cfg(feature = "leaderboard")]
+pub mod leaderboard;
+#[cfg(feature = "midi")]
+pub mod midi;
+pub mod placements;
 pub mod rng;
+pub mod sim;
+pub mod stats;
+#[cfg(feature = "zip")]
+pub mod zip_archive;
 
 #[derive(Debug, Error)]
 pub enum DecodeError {
     #[error("error encountered when decoding the URI encoded LZ string")]
     LzStrDecodeError,
+    #[error("the URL does not have an `r` query parameter holding the replay")]
+    MissingReplayParameter,
     #[error("error encountered when decoding the embedded JSON data: {}", .0)]
-    JsonDecodeError(serde_json::Error),
+    JsonDecodeError(#[source] serde_json::Error),
+    #[error("the event data decoded fine but is {num_bytes} bytes long; events come in pairs of 2 bytes each so this should be a multiple of 4")]
+    MisalignedEventData { num_bytes: usize },
+    #[error("error encountered when decoding the event data: {}", .0)]
+    EventDecodeError(EventDecodeError),
+    #[cfg(feature = "zip")]
+    #[error("error encountered when reading the zip archive: {}", .0)]
+    ZipError(#[from] zip::result::ZipError),
+}
+
+impl From<EventListParseError> for DecodeError {
+    fn from(err: EventListParseError) -> Self {
+        match err {
+            EventListParseError::NotAligned { num_bytes } => {
+                DecodeError::MisalignedEventData { num_bytes }
+            }
+            EventListParseError::EventDecodeError(err) => DecodeError::EventDecodeError(err),
+        }
+    }
 }
 
 #[serde_as]
@@ -51,344 +91,2641 @@ impl JstrisReplay {
     pub fn time(&self) -> Duration {
         self.metadata.game_end - self.metadata.game_start
     }
-}
-
-// TODO: do this the Right Way: switch to having the in memory repr just be a
-// raw vec of `u8`s and do the translation to/from on "field" access
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Deref, DerefMut)]
-pub struct EventList {
-    inner: Vec<Event>,
-}
 
-impl EventList {
-    // TODO: method on `JstrisReplay` that turns these durations into real times
-    // by adding the durations to the game start time
-    pub fn iter(&self) -> impl Iterator<Item = (Input, Duration)> + '_ {
-        let mut base = Duration::milliseconds(0);
-        let mut prev = 0;
-
-        self.inner.iter().map(move |&Event { timestamp, input }| {
-            if timestamp.millis() < prev {
-                // eprint!("{prev} -> {timestamp}; jumping base from {base} to:");
-                base = base + Duration::milliseconds(0x1000);
-                // eprintln!(" {base}");
-            }
-            prev = timestamp.millis();
+    /// Whether the replay's ARR is 0; an unspecified ARR counts as 0 (as in
+    /// [`Metadata::arr_frames`]).
+    ///
+    /// With instant ARR a [`DasLeft`](Input::DasLeft)/[`DasRight`](Input::DasRight)
+    /// takes the piece all the way to the wall and there are no
+    /// [`ArrMove`](Input::ArrMove)s; otherwise a DAS moves the piece one column
+    /// and each further column is an `ArrMove`. Analyses that take a DAS to
+    /// mean "to the wall" (or that map DAS charging onto frames without
+    /// accounting for the auto repeats) are only valid when this is `true`.
+    /// [`JstrisReplay::simulate`] handles both.
+    pub fn uses_instant_arr(&self) -> bool {
+        self.metadata.arr.unwrap_or(0) == 0
+    }
 
-            (input, base + Duration::milliseconds(timestamp.millis() as _))
+    /// [`EventList::time_scale`] that also scales the length of the game.
+    pub fn time_scale(&self, factor: f64) -> Result<JstrisReplay, TimeScaleError> {
+        let data = self.data.time_scale(factor)?;
+        let time =
+            Duration::milliseconds((self.time().num_milliseconds() as f64 * factor).round() as _);
+
+        Ok(JstrisReplay {
+            metadata: Metadata {
+                game_end: self.metadata.game_start + time,
+                ..self.metadata.clone()
+            },
+            data,
         })
     }
-}
 
-impl EventList {
-    pub fn encode(&self) -> Vec<u8> {
-        let mut v = Vec::with_capacity(self.inner.capacity() * 2);
+    /// A copy of the replay with `events` instead of its events; the game
+    /// ends at the new last event (see [`EventList::total_duration`]).
+    ///
+    /// A padding event counts as the last event here, so lists that were
+    /// padded by jstris should have it [removed](JstrisReplay::strip_padding)
+    /// first.
+    pub fn with_events(&self, events: EventList) -> JstrisReplay {
+        JstrisReplay {
+            metadata: Metadata {
+                game_end: self.metadata.game_start + events.total_duration(),
+                ..self.metadata.clone()
+            },
+            data: events,
+        }
+    }
 
-        v.extend(
-            self.inner
-                .iter()
-                .flat_map(|&e| Into::<u16>::into(e).to_be_bytes()),
-        );
+    /// Moves the game (and so every event, as reported by
+    /// [`JstrisReplay::iter_absolute`]) later in time by `by`; the events
+    /// relative to each other and to the start of the game are unchanged.
+    ///
+    /// Shift by `-by` to undo this.
+    pub fn time_shift(&mut self, by: Duration) {
+        self.metadata.game_start += by;
+        self.metadata.game_end += by;
+    }
 
-        if self.inner.len() % 2 == 1 {
-            v.extend([ 0, 0 ]);
-            // TODO: do we really need to pad here?
+    /// Like [`EventList::iter`] but yields real times (offsets added to the
+    /// game start time) instead of offsets.
+    ///
+    /// Useful for lining events up with other logs; the offsets are built up
+    /// the same way as [`EventList::iter`]'s, so they keep increasing past the
+    /// 4096ms the 12-bit timestamps can hold.
+    pub fn iter_absolute(&self) -> AbsoluteEventListIter<'_> {
+        AbsoluteEventListIter {
+            inner: self.data.iter(),
+            game_start: self.metadata.game_start,
         }
+    }
 
-        v
+    /// [`JstrisReplay::iter_absolute`] with the times formatted as RFC 3339
+    /// strings (in UTC, with millisecond precision), ready to be put in logs.
+    pub fn iter_rfc3339(&self) -> impl Iterator<Item = (Input, String)> + '_ {
+        self.iter_absolute()
+            .map(|(input, time)| (input, time.to_rfc3339_opts(SecondsFormat::Millis, true)))
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Error)]
-pub enum EventListParseError {
-    #[error("events are four bytes each; got {num_bytes} bytes which is not a multiple of 4")]
-    NotAligned { num_bytes: usize },
-    #[error("error decoding event: {}", .0)]
-    EventDecodeError(#[from] EventDecodeError),
+impl<'a> IntoIterator for &'a JstrisReplay {
+    type Item = (Input, DateTime<Utc>);
+    type IntoIter = AbsoluteEventListIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_absolute()
+    }
 }
 
-impl TryFrom<Vec<u8>> for EventList {
-    type Error = EventListParseError;
+#[cfg(test)]
+mod test_replay_iter {
+    use super::*;
 
-    fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
-        /*
-        // debug_assert_eq!(std::mem::size_of::<Event>(), 4);
+    #[test]
+    fn into_iter() {
+        let replay = test_util::sample();
 
-        // if bytes.len() % 4 != 0 {
-        //     return Err(EventListParseError::NotAligned { num_bytes: bytes.len() })
-        // }
+        let mut count = 0;
+        let expected = replay.data.iter();
+        for ((input, time), (expected_input, offset)) in (&replay).into_iter().zip(expected) {
+            assert_eq!(input, expected_input);
+            assert_eq!(time, replay.metadata.game_start + offset);
+            count += 1;
+        }
+        assert_eq!(count, replay.data.len());
 
-        // // We want to reuse the `bytes` Vec instead of making a copy.
-        // let elems = bytes.len() / 4;
+        let (first_input, first_time) = (&replay).into_iter().next().unwrap();
+        assert_eq!(first_input, Input::MoveLeft);
+        assert_eq!(
+            first_time - replay.metadata.game_start,
+            Duration::milliseconds(30)
+        );
 
-        // // We're going to do an unsafe cast to produce a `Vec<Event>`; to do
-        // // this we have to make sure the `byte` vec's underlying allocation is
-        // // a multiple of 4 bytes.
-        // bytes.shrink_to_fit();
-        // if bytes.capacity() != elems * 4 { panic!("couldn't shrink the bytes vector!"); }
+        let mut last_drop = None;
+        for (input, time) in &replay {
+            if input == Input::HardDrop {
+                last_drop = Some(time);
+            }
+        }
+        assert_eq!(last_drop, Some(replay.metadata.game_end));
+    }
 
-        // let (ptr, len, cap) = bytes.into_raw_parts();
-        // TODO: store a raw pointer instead and reconstruct the vector on drop
-        // the above is not sound because the alignment of `Event` isn't the
-        // same as `u8` so we can't just dealloc as normal.
-        */
+    #[test]
+    fn absolute_times_roll_over() {
+        let replay = test_util::with_events(&[
+            (Input::MoveLeft, 4000),
+            (Input::HardDrop, 4100),
+            (Input::MoveRight, 8000),
+            (Input::HardDrop, 8300),
+        ]);
+
+        let start = replay.metadata.game_start;
+        let offsets: Vec<_> = replay
+            .iter_absolute()
+            .map(|(_, time)| (time - start).num_milliseconds())
+            .collect();
+        assert_eq!(offsets, [4000, 4100, 8000, 8300]);
+    }
 
-        // TODO: do we really need a multiple of 4 bytes (i.e. pairs of events)?
+    #[test]
+    fn rfc3339() {
+        let replay = test_util::with_events(&[(Input::MoveLeft, 0), (Input::HardDrop, 1234)]);
+        let times: Vec<_> = replay.iter_rfc3339().collect();
+
+        let start = replay.metadata.game_start;
+        assert_eq!(
+            times[0],
+            (
+                Input::MoveLeft,
+                start.to_rfc3339_opts(SecondsFormat::Millis, true)
+            )
+        );
+        assert_eq!(times[0].1, "2023-05-20T00:47:30.931Z");
+        assert_eq!(
+            times[1],
+            (Input::HardDrop, "2023-05-20T00:47:32.165Z".to_string())
+        );
+    }
 
-        if bytes.len() % 4 != 0 {
-            return Err(EventListParseError::NotAligned {
-                num_bytes: bytes.len(),
-            });
-        }
+    #[test]
+    fn instant_arr() {
+        let mut replay = test_util::sample();
+        assert_eq!(replay.metadata.arr, None);
+        assert!(replay.uses_instant_arr());
 
-        let inner = bytes
-            // .array_chunks() // not stable yet
-            .chunks(2)
-            .map(|arr| arr.try_into().unwrap())
-            .map(u16::from_be_bytes)
-            .map(TryInto::try_into)
-            .collect::<Result<_, _>>()?;
+        replay.metadata.arr = Some(0);
+        assert!(replay.uses_instant_arr());
 
-        // Ok(EventList { inner, _encoded: RefCell::new(None) })
-        Ok(EventList { inner })
+        replay.metadata.arr = Some(33);
+        assert!(!replay.uses_instant_arr());
     }
-}
 
-// impl DerefMut for EventList {
-//     fn deref_mut(&mut self) -> &mut Self::Target {
-//         // Drop the cached encoded form if there's potential the actual data
-//         // will be modified:
-//         self._encoded.get_mut().take();
-//         &mut self.inner
-//     }
-// }
+    #[test]
+    fn with_events() {
+        let replay = test_util::sample();
+        let shorter = replay.with_events(replay.data.truncate_to_events(10));
 
-// impl AsRef<[u8]> for EventList {
-//     fn as_ref(&self) -> &[u8] {
-//         if let Some(inner) = *self._encoded.borrow() {
-//             inner
-//         } else {
-//             *self._encoded.borrow_mut() = Some(self.encode());
-//             self._encoded.borrow()
-//         }
-//     }
-// }
+        assert_eq!(shorter.data.len(), 10);
+        assert_eq!(shorter.time(), shorter.data.total_duration());
+        assert!(shorter.time() < replay.time());
+        assert_eq!(shorter.metadata.game_start, replay.metadata.game_start);
+        assert_eq!(shorter.metadata.seed, replay.metadata.seed);
+    }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct Event {
-    timestamp: TwelveBitMillisecondTimestamp,
-    input: Input,
-}
+    #[test]
+    fn time_shift() {
+        let original = test_util::sample();
+        let by = Duration::milliseconds(1000);
+
+        let mut shifted = original.clone();
+        shifted.time_shift(by);
+        assert_eq!(shifted.time(), original.time());
+        assert_eq!(shifted.data, original.data);
+        for ((_, shifted), (_, original)) in shifted.iter_absolute().zip(original.iter_absolute()) {
+            assert_eq!(shifted - original, by);
+        }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Error)]
-pub enum EventDecodeError {}
+        shifted.time_shift(-by);
+        assert_eq!(shifted, original);
+    }
+}
 
-impl TryFrom<u16> for Event {
-    type Error = EventDecodeError;
+// TODO: do this the Right Way: switch to having the in memory repr just be a
+// raw vec of `u8`s and do the translation to/from on "field" access
+/// The inputs in a replay.
+///
+/// Derefs to the raw `Vec<Event>`; note that mutating that directly can
+/// leave the list inconsistent (the timestamps are 12-bit and wrap, so an
+/// event's time depends on every event before it). Prefer
+/// [`EventList::append_event`] and [`EventList::insert_event`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deref)]
+pub struct EventList {
+    #[deref]
+    inner: Vec<Event>,
+    offsets: OffsetCache,
+}
 
-    fn try_from(value: u16) -> Result<Self, Self::Error> {
-        let delay = value >> 4;
-        let input = (value & 0x0F) as u8;
+impl DerefMut for EventList {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // Drop the cached offsets if there's potential the events will be
+        // modified:
+        self.offsets.invalidate();
+        &mut self.inner
+    }
+}
 
-        // TODO: validation
+/// The offset of each event in an [`EventList`]; see
+/// [`EventList::time_of_event`].
+///
+/// Derived entirely from the events so it's ignored when comparing and
+/// hashing lists.
+#[derive(Clone, Default)]
+struct OffsetCache(OnceLock<Vec<Duration>>);
+
+impl OffsetCache {
+    fn invalidate(&mut self) {
+        self.0.take();
+    }
+}
 
-        Ok(Event {
-            timestamp: delay.try_into().unwrap(),
-            input: Input::from_raw(input),
-        })
+impl Debug for OffsetCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OffsetCache")
     }
 }
 
-impl From<Event> for u16 {
-    fn from(ev: Event) -> u16 {
-        ev.timestamp.millis() << 4 | (ev.input as u16)
+impl PartialEq for OffsetCache {
+    fn eq(&self, _: &Self) -> bool {
+        true
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize_repr, Deserialize_repr)]
-#[repr(u8)]
-pub enum Input {
-    MoveLeft = 0,
-    MoveRight = 1,
-    DasLeft = 2,
-    DasRight = 3,
-    RotateLeft = 4,
-    RotateRight = 5,
-    Rotate180 = 6,
-    HardDrop = 7,
-    SoftDropBeginEnd = 8,
-    GravityStep = 9,
-    HoldBlock = 10,
-    GarbageAdd = 11,
-    SGarbageAdd = 12,
-    RedBarSet = 13,
-    ArrMove = 14,
-    Aux = 15,
+impl Eq for OffsetCache {}
+
+impl Hash for OffsetCache {
+    fn hash<H: std::hash::Hasher>(&self, _: &mut H) {}
 }
 
-// impl Display for Input {
-//     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-//         use Input::*;
-//         match self {
-//             MoveLeft => "",
-//             MoveRight => todo!(),
-//             DasLeft => todo!(),
-//             DasRight => todo!(),
-//             RotateLeft => todo!(),
-//             RotateRight => todo!(),
-//             Rotate180 => todo!(),
-//             HardDrop => todo!(),
-//             SoftDropBeginEnd => todo!(),
-//             GravityStep => todo!(),
-//             HoldBlock => todo!(),
-//             GarbageAdd => todo!(),
-//             SGarbageAdd => todo!(),
-//             RedBarSet => todo!(),
-//             ArrMove => todo!(),
-//             Aux => todo!(),
-//         }
-//     }
-// }
+impl EventList {
+    /// Yields each input along with its offset from the start of the game.
+    ///
+    /// Timestamps are the offset mod 4096ms; a timestamp smaller than the one
+    /// before it means the offset wrapped. There's no explicit rollover event
+    /// (the sample replay has no aux events at all), so a real gap of 4096ms
+    /// or more between two events can't be detected: it's read as a gap 4096ms
+    /// (or some multiple of that) shorter, which shifts every later event back
+    /// by that much. See [`EventList::MAX_GAP_MILLIS`].
+    ///
+    /// The one special word is `0xFFF0` (timestamp `0xFFF`, input 0), which
+    /// jstris pads the end of the data with. It's yielded like any other event,
+    /// as a `MoveLeft` at or after the end of the game; see
+    /// [`JstrisReplay::strip_padding`].
+    ///
+    /// See [`JstrisReplay::iter_absolute`] for real times.
+    pub fn iter(&self) -> EventListIter<'_> {
+        EventListIter {
+            inner: self.inner.iter(),
+            base: Duration::milliseconds(0),
+            prev: 0,
+        }
+    }
 
-impl Input {
-    #[inline]
-    pub fn from_raw(raw: u8) -> Self {
-        assert!(raw & 0xF0 == 0);
-        // debug_assert!(disc) variant_count == 16
+    /// The offset from the start of the game of the event at `idx`; the same
+    /// as [`EventList::iter`] would yield for it.
+    ///
+    /// The offsets of all the events are computed on the first call and kept
+    /// until the list is next modified, so lookups after that are O(1).
+    pub fn time_of_event(&self, idx: usize) -> Option<Duration> {
+        self.offsets
+            .0
+            .get_or_init(|| self.iter().map(|(_, offset)| offset).collect())
+            .get(idx)
+            .copied()
+    }
 
-        unsafe { core::mem::transmute(raw) }
+    /// The inputs whose offset from the start of the game falls in
+    /// `[start, end)`, along with those offsets.
+    pub fn inputs_between(&self, start: Duration, end: Duration) -> Vec<(Input, Duration)> {
+        self.iter()
+            .skip_while(|&(_, offset)| offset < start)
+            .take_while(|&(_, offset)| offset < end)
+            .collect()
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize_repr, Deserialize_repr)]
-#[repr(u8)]
-pub enum AuxInput {
-    Afk = 0,
-    BlockSet = 1,
-    MoveTo = 2,
-    Randomizer = 3,
-    MatrixMod = 4,
-    WideGarbageMod = 5,
+#[cfg(test)]
+mod test_inputs_between {
+    use super::*;
+    use Input::*;
+
+    #[test]
+    fn sub_range() {
+        let ms = Duration::milliseconds;
+        let replay = test_util::with_events(&[
+            (MoveLeft, 100),
+            (HardDrop, 3900),
+            (RotateLeft, 4000),
+            // The timestamps roll over here.
+            (MoveRight, 4200),
+            (HardDrop, 4300),
+        ]);
+
+        assert_eq!(
+            replay.data.inputs_between(ms(3900), ms(4300)),
+            [
+                (HardDrop, ms(3900)),
+                (RotateLeft, ms(4000)),
+                (MoveRight, ms(4200))
+            ]
+        );
+        assert_eq!(replay.data.inputs_between(ms(101), ms(3900)), []);
+        assert_eq!(replay.data.inputs_between(ms(0), ms(5000)).len(), 5);
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct TwelveBitMillisecondTimestamp(u16);
+#[cfg(test)]
+mod test_time_of_event {
+    use super::*;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Error)]
-pub enum TwelveBitMillisecondTimestampConversionError<Source: Display = u16> {
-    TooBig { duration: Source },
-    Invalid { duration: Source },
-}
+    #[test]
+    fn matches_iter() {
+        let events = test_util::sample().data;
+        for (idx, (_, offset)) in events.iter().enumerate() {
+            assert_eq!(events.time_of_event(idx), Some(offset));
+        }
+        assert_eq!(events.time_of_event(events.len()), None);
+    }
 
-impl TryFrom<Duration> for TwelveBitMillisecondTimestamp {
-    type Error = TwelveBitMillisecondTimestampConversionError<Duration>;
+    #[test]
+    fn invalidated_by_mutation() {
+        let ms = Duration::milliseconds;
+        let mut events = EventList::from_events([(Input::HardDrop, ms(100))]).unwrap();
+        assert_eq!(events.time_of_event(0), Some(ms(100)));
 
-    fn try_from(duration: Duration) -> Result<Self, Self::Error> {
-        use TwelveBitMillisecondTimestampConversionError as E;
+        events.append_event(Input::HardDrop, ms(4000)).unwrap();
+        assert_eq!(events.time_of_event(1), Some(ms(4000)));
 
-        match duration.num_milliseconds() {
-            val if val.is_positive() => Ok(Self(val as u16)),
-            _ => Err(E::Invalid { duration }),
-        }
+        events.insert_event(Input::MoveLeft, ms(50)).unwrap();
+        assert_eq!(events.time_of_event(2), Some(ms(4000)));
+
+        events.remove(0);
+        assert_eq!(events.time_of_event(0), Some(ms(100)));
+        assert_eq!(events.time_of_event(2), None);
+
+        // The cache doesn't take part in comparisons.
+        let fresh = EventList::from_events(events.iter()).unwrap();
+        assert_eq!(events, fresh);
     }
-}
 
-impl From<TwelveBitMillisecondTimestamp> for Duration {
-    fn from(timestamp: TwelveBitMillisecondTimestamp) -> Duration {
-        Duration::milliseconds(timestamp.0 as _)
+    #[test]
+    fn extended_by_append() {
+        let ms = Duration::milliseconds;
+        let mut events = EventList::from_events([]).unwrap();
+        for i in 0..20 {
+            events.append_event(Input::HardDrop, ms(i * 1500)).unwrap();
+        }
+
+        let fresh = EventList::from_events(events.iter()).unwrap();
+        for idx in 0..20 {
+            assert_eq!(events.time_of_event(idx), fresh.time_of_event(idx));
+        }
+        assert_eq!(events.time_of_event(19), Some(ms(19 * 1500)));
     }
 }
 
-impl TryFrom<u16> for TwelveBitMillisecondTimestamp {
-    type Error = TwelveBitMillisecondTimestampConversionError;
+impl EventList {
+    /// Groups together inputs that happened at the same time (i.e. in the
+    /// same millisecond), in order.
+    pub fn same_tick_groups(&self) -> impl Iterator<Item = (Duration, Vec<Input>)> + '_ {
+        let mut events = self.iter().peekable();
 
-    fn try_from(value: u16) -> Result<Self, Self::Error> {
-        use TwelveBitMillisecondTimestampConversionError as E;
+        std::iter::from_fn(move || {
+            let (input, offset) = events.next()?;
 
-        match value {
-            0..=0x0F_FF => Ok(Self(value)),
-            _ => Err(E::TooBig { duration: value }),
-        }
+            let mut group = vec![input];
+            while let Some((input, _)) = events.next_if(|&(_, o)| o == offset) {
+                group.push(input);
+            }
+
+            Some((offset, group))
+        })
     }
 }
 
-impl TwelveBitMillisecondTimestamp {
-    pub const fn millis(self) -> u16 {
-        self.0
+impl EventList {
+    /// Groups events by the 4096ms window (i.e. the span of a full cycle of
+    /// the 12-bit timestamps) they fall in; yields the window's index and the
+    /// indices of its events.
+    pub fn rollover_windows(&self) -> impl Iterator<Item = (u32, Range<usize>)> + '_ {
+        let window = |offset: Duration| (offset.num_milliseconds() / 0x1000) as u32;
+        let mut events = self
+            .iter()
+            .map(move |(_, offset)| window(offset))
+            .enumerate()
+            .peekable();
+
+        std::iter::from_fn(move || {
+            let (start, current) = events.next()?;
+
+            let mut end = start + 1;
+            while let Some((idx, _)) = events.next_if(|&(_, w)| w == current) {
+                end = idx + 1;
+            }
+
+            Some((current, start..end))
+        })
     }
 }
 
-impl Display for TwelveBitMillisecondTimestamp {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", Into::<Duration>::into(*self))
+#[cfg(test)]
+mod test_grouping {
+    use super::*;
+    use Input::*;
+
+    #[test]
+    fn same_tick_groups() {
+        let replay = test_util::with_events(&[
+            (HoldBlock, 100),
+            (DasLeft, 100),
+            (RotateRight, 150),
+            (HardDrop, 200),
+            (DasLeft, 200),
+            (MoveRight, 200),
+        ]);
+        let groups: Vec<_> = replay
+            .data
+            .same_tick_groups()
+            .map(|(offset, inputs)| (offset.num_milliseconds(), inputs))
+            .collect();
+
+        assert_eq!(
+            groups,
+            [
+                (100, vec![HoldBlock, DasLeft]),
+                (150, vec![RotateRight]),
+                (200, vec![HardDrop, DasLeft, MoveRight]),
+            ]
+        );
     }
-}
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
-pub struct Metadata {
-    #[serde(rename = "softDropId")]
-    pub soft_drop_id: SoftDropSpeed,
+    #[test]
+    fn rollover_windows() {
+        let replay = test_util::with_events(&[
+            (MoveLeft, 100),
+            (HardDrop, 4000),
+            (HardDrop, 4095),
+            (DasLeft, 4096),
+            (HardDrop, 5000),
+        ]);
+        let windows: Vec<_> = replay.data.rollover_windows().collect();
+
+        assert_eq!(windows, [(0, 0..3), (1, 3..5)]);
+    }
+}
 
-    #[serde(rename = "gameStart")]
-    #[serde(with = "ts_milliseconds")]
-    pub game_start: DateTime<Utc>,
-    #[serde(rename = "gameEnd")]
-    #[serde(with = "ts_milliseconds")]
-    pub game_end: DateTime<Utc>,
+/// Iterator over the inputs in an [`EventList`] and their offsets from the
+/// start of the game; see [`EventList::iter`].
+#[derive(Debug, Clone)]
+pub struct EventListIter<'a> {
+    inner: std::slice::Iter<'a, Event>,
+    base: Duration,
+    prev: u16,
+}
 
-    pub seed: GameSeed,
+impl Iterator for EventListIter<'_> {
+    type Item = (Input, Duration);
 
-    #[serde(rename = "bs")]
-    pub block_skin: BlockSkin,
+    fn next(&mut self) -> Option<Self::Item> {
+        let &Event { timestamp, input } = self.inner.next()?;
 
-    #[serde(rename = "se")]
-    pub sound_effects: SoundEffects,
+        if timestamp.millis() < self.prev {
+            // eprint!("{prev} -> {timestamp}; jumping base from {base} to:");
+            self.base = self.base + Duration::milliseconds(0x1000);
+            // eprintln!(" {base}");
+        }
+        self.prev = timestamp.millis();
 
-    /// Delayed Auto Shift
-    ///
-    /// See [here](https://tetris.wiki/DAS).
-    #[serde(default)]
-    pub das: u16, // jstris allows [0, 4999]
+        Some((
+            input,
+            self.base + Duration::milliseconds(timestamp.millis() as _),
+        ))
+    }
 
-    /// Auto Repeat Rate
-    #[serde(default)]
-    pub arr: u16, // jstris allows [0, 4999]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
 
-    #[serde(rename = "m")]
-    pub game_mode: GameMode, // ???
+/// Iterator over the inputs in a [`JstrisReplay`] and the times at which they
+/// happened; see [`JstrisReplay::iter_absolute`].
+#[derive(Debug, Clone)]
+pub struct AbsoluteEventListIter<'a> {
+    inner: EventListIter<'a>,
+    game_start: DateTime<Utc>,
+}
 
-    #[serde(rename = "v")]
-    pub version: ExpectedJstrisReplayVersion<3, 0>, // we're compatible with 3.0 and up (tested through 3.3)
+impl Iterator for AbsoluteEventListIter<'_> {
+    type Item = (Input, DateTime<Utc>);
 
-    pub r: Option<u16>, // ???
+    fn next(&mut self) -> Option<Self::Item> {
+        let (input, offset) = self.inner.next()?;
+        Some((input, self.game_start + offset))
+    }
 
-    // todo: bbs? big blocks?
-    pub bbs: Option<u8>, // todo: this should actually be a bool on our end but
-                         // not on the wire?
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct ExpectedJstrisReplayVersion<const MAJOR: u8 = 3, const MINOR: u8 = 3> {
-    actual_minor: u8,
+/// An event along with its payload (if it has one); see
+/// [`EventList::iter_decoded`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DecodedEvent {
+    /// An input that doesn't [require a payload](Input::requires_payload).
+    Plain(Input),
+    /// A [`GarbageAdd`](Input::GarbageAdd),
+    /// [`SGarbageAdd`](Input::SGarbageAdd), or
+    /// [`RedBarSet`](Input::RedBarSet) and the word following it.
+    WithPayload(Input, u16),
+    /// An [`Aux`](Input::Aux) event: the kind of aux event (the low nibble of
+    /// the following word) and the rest of that word. Only produced for the
+    /// kinds that [fit in that one word](AuxInput::fits_in_one_word).
+    Aux(AuxInput, u16),
 }
 
-impl<const MAJ: u8, const MIN: u8> Debug for ExpectedJstrisReplayVersion<MAJ, MIN> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("JstrisReplayVersion")
-            .field("major_ver", &MAJ)
-            .field("minor_ver", &self.actual_minor)
-            .finish()
+impl DecodedEvent {
+    pub const fn input(self) -> Input {
+        match self {
+            DecodedEvent::Plain(input) | DecodedEvent::WithPayload(input, _) => input,
+            DecodedEvent::Aux(..) => Input::Aux,
+        }
     }
 }
 
-impl<const MAJ: u8, const MIN: u8> Display for ExpectedJstrisReplayVersion<MAJ, MIN> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{MAJ}.{}", self.actual_minor)
-    }
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Error)]
+pub enum DecodedEventError {
+    #[error("event {index} ({input}) is missing its payload")]
+    MissingPayload { index: usize, input: Input },
+    #[error("event {index} is an aux event of unknown kind {code}")]
+    UnknownAux { index: usize, code: u8 },
+    #[error("event {index} is a {aux:?} aux event, whose size isn't known")]
+    UnknownAuxSize { index: usize, aux: AuxInput },
 }
 
-impl<const MIN: u8> Default for ExpectedJstrisReplayVersion<3, MIN> {
-    fn default() -> Self {
+/// Iterator over the events in an [`EventList`] with their payloads folded
+/// in; see [`EventList::iter_decoded`].
+#[derive(Debug, Clone)]
+pub struct DecodedEventIter<'a> {
+    inner: EventListIter<'a>,
+    index: usize,
+    /// Set after an error that leaves us not knowing where the next event
+    /// starts.
+    lost: bool,
+}
+
+impl Iterator for DecodedEventIter<'_> {
+    type Item = Result<(DecodedEvent, Duration), DecodedEventError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.lost {
+            return None;
+        }
+        let (input, offset) = self.inner.next()?;
+        let index = self.index;
+        self.index += 1;
+
+        if !input.requires_payload() {
+            return Some(Ok((DecodedEvent::Plain(input), offset)));
+        }
+
+        // The payload isn't an event so it shouldn't count towards the
+        // timestamp wrapping; take it from the underlying events directly.
+        let Some(&payload) = self.inner.inner.next() else {
+            return Some(Err(DecodedEventError::MissingPayload { index, input }));
+        };
+        self.index += 1;
+        let payload = u16::from(payload);
+
+        if !input.is_aux() {
+            return Some(Ok((DecodedEvent::WithPayload(input, payload), offset)));
+        }
+
+        let code = (payload & 0x0F) as u8;
+        let err = match AuxInput::from_code(code) {
+            Some(aux) if aux.fits_in_one_word() => {
+                return Some(Ok((DecodedEvent::Aux(aux, payload >> 4), offset)))
+            }
+            Some(aux) => DecodedEventError::UnknownAuxSize { index, aux },
+            None => DecodedEventError::UnknownAux { index, code },
+        };
+        // Rather than guessing how many words to skip, stop.
+        self.lost = true;
+        Some(Err(err))
+    }
+}
+
+impl EventList {
+    /// Like [`EventList::iter`] but with the payload words that follow some
+    /// inputs (see [`Input::requires_payload`]) folded into their event
+    /// instead of being read as events of their own.
+    ///
+    /// Errors refer to events by their index in the list (payloads
+    /// included). The payload layouts are unverified, like
+    /// [`Input::requires_payload`]'s.
+    ///
+    /// Aux events whose size isn't known (see [`AuxInput::fits_in_one_word`])
+    /// give an error, as do aux codes that aren't an [`AuxInput`]; nothing is
+    /// yielded after either since there's no telling where the next event
+    /// starts.
+    pub fn iter_decoded(&self) -> DecodedEventIter<'_> {
+        DecodedEventIter {
+            inner: self.iter(),
+            index: 0,
+            lost: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_decoded_events {
+    use super::*;
+    use crate::test_util::{from_words, word};
+
+    #[test]
+    fn plain() {
+        let sample = test_util::sample().data;
+        let decoded: Vec<_> = sample.iter_decoded().map(Result::unwrap).collect();
+
+        assert_eq!(
+            decoded,
+            sample
+                .iter()
+                .map(|(input, offset)| (DecodedEvent::Plain(input), offset))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn payloads() {
+        let ms = Duration::milliseconds;
+        let list = from_words(&[
+            word(100, Input::GarbageAdd),
+            0x1234,
+            word(200, Input::Aux),
+            0xABC0 | AuxInput::Randomizer.code() as u16,
+            // A payload with a small "timestamp" doesn't cause a wrap:
+            word(300, Input::RedBarSet),
+            0x0005,
+            word(400, Input::HardDrop),
+        ]);
+
+        assert_eq!(
+            list.iter_decoded().collect::<Vec<_>>(),
+            [
+                Ok((
+                    DecodedEvent::WithPayload(Input::GarbageAdd, 0x1234),
+                    ms(100)
+                )),
+                Ok((DecodedEvent::Aux(AuxInput::Randomizer, 0xABC), ms(200))),
+                Ok((DecodedEvent::WithPayload(Input::RedBarSet, 5), ms(300))),
+                Ok((DecodedEvent::Plain(Input::HardDrop), ms(400))),
+            ]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let list = from_words(&[word(0, Input::HardDrop), word(10, Input::GarbageAdd)]);
+        assert_eq!(
+            list.iter_decoded().last(),
+            Some(Err(DecodedEventError::MissingPayload {
+                index: 1,
+                input: Input::GarbageAdd
+            }))
+        );
+
+        let list = from_words(&[word(0, Input::Aux), 0x000F, word(10, Input::HardDrop)]);
+        assert_eq!(
+            list.iter_decoded().collect::<Vec<_>>(),
+            [Err(DecodedEventError::UnknownAux { index: 0, code: 15 })]
+        );
+    }
+
+    #[test]
+    fn unknown_aux_size() {
+        for aux in [AuxInput::BlockSet, AuxInput::MoveTo, AuxInput::MatrixMod] {
+            let list = from_words(&[
+                word(0, Input::HardDrop),
+                word(10, Input::Aux),
+                0x0120 | aux.code() as u16,
+                word(20, Input::HardDrop),
+            ]);
+            assert_eq!(
+                list.iter_decoded().skip(1).collect::<Vec<_>>(),
+                [Err(DecodedEventError::UnknownAuxSize { index: 1, aux })]
+            );
+        }
+    }
+}
+
+impl EventList {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut v = Vec::with_capacity(self.inner.capacity() * 2);
+
+        v.extend(
+            self.inner
+                .iter()
+                .flat_map(|&e| Into::<u16>::into(e).to_be_bytes()),
+        );
+
+        // The event data is always a whole number of pairs of events, so an
+        // odd list needs an extra event. jstris pads its own replays with
+        // `0xFFF0` (see the sample replay's last word), so we do too. See
+        // `JstrisReplay::strip_padding` for undoing this when decoding.
+        if self.inner.len() % 2 == 1 {
+            v.extend(0xFFF0u16.to_be_bytes());
+        }
+
+        v
+    }
+
+    /// The length of [`EventList::encode`]'s output, without encoding.
+    pub fn byte_len(&self) -> usize {
+        // Padded to an even number of events.
+        self.inner.len().next_multiple_of(2) * 2
+    }
+}
+
+impl EventList {
+    /// A hex dump of [`EventList::encode`]'s output with each 2 byte word
+    /// annotated with the (raw, 12-bit) timestamp and input it decodes to; one
+    /// word per line.
+    pub fn hex_dump(&self) -> String {
+        let encoded = self.encode();
+
+        let mut out = String::new();
+        for (idx, word) in encoded.chunks(2).enumerate() {
+            let [hi, lo] = [word[0], word[1]];
+            let _ = write!(out, "{:04x}: {hi:02x} {lo:02x}", idx * 2);
+
+            if idx < self.inner.len() {
+                let Event { timestamp, input } = self.inner[idx];
+                let _ = writeln!(out, "  {:>4}ms {input:?}", timestamp.millis());
+            } else {
+                let _ = writeln!(out, "  (padding)");
+            }
+        }
+
+        out
+    }
+}
+
+impl JstrisReplay {
+    /// Removes the final event if it's padding rather than a real input;
+    /// returns whether an event was removed.
+    ///
+    /// The event data always holds an even number of events so lists with
+    /// an odd number of events get an extra one when encoded: `0xFFF0`, from
+    /// both jstris and [`EventList::encode`] (older versions of this crate
+    /// used `0x0000`, which is accepted too). It decodes as a
+    /// [`MoveLeft`](Input::MoveLeft) at a timestamp of 4095, so decoding and
+    /// re-encoding such a list without stripping the padding adds an input.
+    ///
+    /// The final event is taken to be padding if it's one of those two
+    /// words and it happens at or after the end of the game. A real
+    /// `MoveLeft` at such a time is indistinguishable from padding and will
+    /// be removed too; padding that lands before the end of the game (i.e.
+    /// when the last real input is more than 4096ms before the end) is not
+    /// detected.
+    pub fn strip_padding(&mut self) -> bool {
+        if !self.ends_with_padding() {
+            return false;
+        }
+
+        self.data.pop();
+        true
+    }
+
+    /// Whether [`JstrisReplay::strip_padding`] would remove the final event.
+    pub(crate) fn ends_with_padding(&self) -> bool {
+        let Some(&last) = self.data.inner.last() else {
+            return false;
+        };
+        if !self.data.inner.len().is_multiple_of(2) || !matches!(u16::from(last), 0x0000 | 0xFFF0) {
+            return false;
+        }
+
+        self.data.total_duration() >= self.time()
+    }
+}
+
+#[cfg(test)]
+mod test_encode {
+    use super::*;
+
+    #[test]
+    fn strip_padding() {
+        let mut odd = test_util::with_events(&[
+            (Input::MoveRight, 100),
+            (Input::HardDrop, 200),
+            (Input::HardDrop, 300),
+        ]);
+        odd.metadata.game_end = odd.metadata.game_start + Duration::milliseconds(300);
+        let json = serde_json::to_string(&odd).unwrap();
+
+        let padded = decode_json(&json).unwrap();
+        assert_eq!(padded.data.len(), 4);
+        assert_eq!(
+            padded.data.iter().last(),
+            Some((Input::MoveLeft, Duration::milliseconds(4095)))
+        );
+
+        let options = DecodeOptions {
+            strip_padding: true,
+        };
+        let stripped = decode_json_with(&json, options).unwrap();
+        assert_eq!(stripped, odd);
+
+        // Round trips don't accumulate events:
+        let json = serde_json::to_string(&stripped).unwrap();
+        assert_eq!(decode_json_with(json, options).unwrap(), odd);
+
+        // Even lists aren't padded so there's nothing to strip:
+        let mut even = test_util::with_events(&[(Input::HardDrop, 200), (Input::MoveLeft, 4096)]);
+        even.metadata.game_end = even.metadata.game_start + Duration::milliseconds(5000);
+        assert!(!even.strip_padding());
+    }
+
+    #[test]
+    fn odd_round_trip() {
+        let mut odd = test_util::with_events(&[(Input::MoveRight, 100)]);
+        odd.metadata.game_end = odd.metadata.game_start + Duration::milliseconds(100);
+        let encoded = odd.data.encode();
+        assert_eq!(encoded, [0x06, 0x41, 0xFF, 0xF0]);
+
+        // The padding comes back as an extra event, which is the same padding
+        // jstris writes and so is stripped the same way.
+        let mut decoded = JstrisReplay {
+            data: EventList::try_from(encoded).unwrap(),
+            ..odd.clone()
+        };
+        assert_eq!(decoded.data.len(), 2);
+        assert!(decoded.strip_padding());
+        assert_eq!(decoded, odd);
+    }
+
+    #[test]
+    fn hex_dump() {
+        let dump = test_util::sample().data.hex_dump();
+        let mut lines = dump.lines();
+
+        assert_eq!(lines.next(), Some("0000: 01 e0    30ms MoveLeft"));
+        assert_eq!(lines.next(), Some("0002: 03 e7    62ms HardDrop"));
+        assert_eq!(dump.lines().count(), 334);
+
+        let odd = test_util::with_events(&[(Input::HoldBlock, 4095)]).data;
+        assert_eq!(
+            odd.hex_dump(),
+            "0000: ff fa  4095ms HoldBlock\n0002: ff f0  (padding)\n"
+        );
+    }
+
+    #[test]
+    fn strip_jstris_padding() {
+        let mut sample = test_util::sample();
+        assert_eq!(
+            sample.data.iter().last(),
+            Some((Input::MoveLeft, Duration::milliseconds(16383)))
+        );
+
+        assert!(sample.strip_padding());
+        assert_eq!(sample.data.len(), 333);
+        assert_eq!(sample.data.total_duration(), sample.time());
+        assert!(!sample.strip_padding());
+    }
+
+    #[test]
+    fn byte_len() {
+        for n in [0, 1, 2, 3, 10, 11] {
+            let events: Vec<_> = (0..n).map(|i| (Input::HardDrop, i * 10)).collect();
+            let list = test_util::with_events(&events).data;
+
+            assert_eq!(list.byte_len(), list.encode().len(), "{n} events");
+        }
+
+        let sample = test_util::sample().data;
+        assert_eq!(sample.byte_len(), sample.encode().len());
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Error)]
+pub enum EventListParseError {
+    #[error("events are four bytes each; got {num_bytes} bytes which is not a multiple of 4")]
+    NotAligned { num_bytes: usize },
+    #[error("error decoding event: {}", .0)]
+    EventDecodeError(#[from] EventDecodeError),
+}
+
+impl TryFrom<Vec<u8>> for EventList {
+    type Error = EventListParseError;
+
+    fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
+        /*
+        // debug_assert_eq!(std::mem::size_of::<Event>(), 4);
+
+        // if bytes.len() % 4 != 0 {
+        //     return Err(EventListParseError::NotAligned { num_bytes: bytes.len() })
+        // }
+
+        // // We want to reuse the `bytes` Vec instead of making a copy.
+        // let elems = bytes.len() / 4;
+
+        // // We're going to do an unsafe cast to produce a `Vec<Event>`; to do
+        // // this we have to make sure the `byte` vec's underlying allocation is
+        // // a multiple of 4 bytes.
+        // bytes.shrink_to_fit();
+        // if bytes.capacity() != elems * 4 { panic!("couldn't shrink the bytes vector!"); }
+
+        // let (ptr, len, cap) = bytes.into_raw_parts();
+        // TODO: store a raw pointer instead and reconstruct the vector on drop
+        // the above is not sound because the alignment of `Event` isn't the
+        // same as `u8` so we can't just dealloc as normal.
+        */
+
+        EventList::try_from(&bytes[..])
+    }
+}
+
+impl TryFrom<&[u8]> for EventList {
+    type Error = EventListParseError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        // Pairs of events; see `EventList::encode`. Nothing jstris produces has
+        // an odd number of events so anything else is taken to be corrupt.
+        if !bytes.len().is_multiple_of(4) {
+            return Err(EventListParseError::NotAligned {
+                num_bytes: bytes.len(),
+            });
+        }
+
+        let inner = EventList::decode_iter(bytes).collect::<Result<_, _>>()?;
+
+        // Ok(EventList { inner, _encoded: RefCell::new(None) })
+        Ok(EventList {
+            inner,
+            offsets: OffsetCache::default(),
+        })
+    }
+}
+
+impl EventList {
+    /// Decodes events from `bytes` one at a time, without collecting them
+    /// into a list; for a single pass over a long replay.
+    ///
+    /// Unlike `EventList::try_from`, misalignment is only reported once the
+    /// events before it have been yielded: if `bytes` doesn't end on a whole
+    /// pair of events the last item is an [`EventListParseError::NotAligned`].
+    ///
+    /// Note that the events' timestamps are the raw 12-bit ones.
+    pub fn decode_iter(
+        bytes: &[u8],
+    ) -> impl Iterator<Item = Result<Event, EventListParseError>> + '_ {
+        // Pairs of events; see `EventList::encode`.
+        let (pairs, rest) = bytes.split_at(bytes.len() - bytes.len() % 4);
+
+        pairs
+            .chunks_exact(2)
+            .map(|word| Ok(Event::try_from(u16::from_be_bytes([word[0], word[1]]))?))
+            .chain(
+                (!rest.is_empty()).then_some(Err(EventListParseError::NotAligned {
+                    num_bytes: bytes.len(),
+                })),
+            )
+    }
+}
+
+/// See [`EventList::from_events`].
+impl TryFrom<Vec<(Input, Duration)>> for EventList {
+    type Error = FromEventsError;
+
+    fn try_from(events: Vec<(Input, Duration)>) -> Result<Self, Self::Error> {
+        EventList::from_events(events)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Error)]
+pub enum FromEventsError {
+    #[error("event {index} happens before the event preceding it")]
+    NotMonotonic { index: usize },
+    #[error("event {index} happens {gap} after the event preceding it; gaps of more than {}ms can't be represented", EventList::MAX_GAP_MILLIS)]
+    GapTooLarge { index: usize, gap: Duration },
+}
+
+impl EventList {
+    /// The largest gap (in milliseconds) between consecutive events that can
+    /// be represented.
+    ///
+    /// Timestamps are 12 bits and wrap; a wrap is detected when a timestamp
+    /// is smaller than the one before it (see [`EventList::iter`]) which means
+    /// gaps of 4096ms or more are ambiguous.
+    pub const MAX_GAP_MILLIS: u16 = 0x0FFF;
+
+    /// Builds an event list from inputs and their offsets from the start of
+    /// the game (i.e. the inverse of [`EventList::iter`]).
+    ///
+    /// Offsets are truncated to milliseconds.
+    pub fn from_events(
+        events: impl IntoIterator<Item = (Input, Duration)>,
+    ) -> Result<Self, FromEventsError> {
+        let mut prev = Duration::milliseconds(0);
+
+        let inner = events
+            .into_iter()
+            .enumerate()
+            .map(|(index, (input, offset))| {
+                let gap = offset - prev;
+                if gap < Duration::zero() {
+                    return Err(FromEventsError::NotMonotonic { index });
+                }
+                if gap > Duration::milliseconds(Self::MAX_GAP_MILLIS as _) {
+                    return Err(FromEventsError::GapTooLarge { index, gap });
+                }
+                prev = offset;
+
+                let millis = (offset.num_milliseconds() % 0x1000) as u16;
+                Ok(Event {
+                    timestamp: TwelveBitMillisecondTimestamp(millis),
+                    input,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(EventList {
+            inner,
+            offsets: OffsetCache::default(),
+        })
+    }
+}
+
+impl EventList {
+    /// Adds an input to the end of the list.
+    ///
+    /// `offset` (from the start of the game, like [`EventList::iter`]) must
+    /// not be before the last event and must be within
+    /// [`EventList::MAX_GAP_MILLIS`] of it.
+    ///
+    /// Uses (and extends) the offsets cached by [`EventList::time_of_event`],
+    /// so only the first append after the list is otherwise modified is O(n);
+    /// appending in a loop is O(1) per event.
+    pub fn append_event(&mut self, input: Input, offset: Duration) -> Result<(), FromEventsError> {
+        let index = self.inner.len();
+        let last = index
+            .checked_sub(1)
+            .and_then(|idx| self.time_of_event(idx))
+            .unwrap_or_else(Duration::zero);
+        let gap = offset - last;
+        if gap < Duration::zero() {
+            return Err(FromEventsError::NotMonotonic { index });
+        }
+        if gap > Duration::milliseconds(Self::MAX_GAP_MILLIS as _) {
+            return Err(FromEventsError::GapTooLarge { index, gap });
+        }
+
+        let millis = (offset.num_milliseconds() % 0x1000) as u16;
+        if let Some(offsets) = self.offsets.0.get_mut() {
+            offsets.push(offset);
+        }
+        self.inner.push(Event {
+            timestamp: TwelveBitMillisecondTimestamp(millis),
+            input,
+        });
+
+        Ok(())
+    }
+
+    /// A new list with only the first `n` events (or all of them, if there
+    /// are fewer than `n`).
+    pub fn truncate_to_events(&self, n: usize) -> EventList {
+        // Whether an event's timestamp rolled over only depends on the events
+        // before it, so a prefix of the raw events is still a valid list.
+        EventList {
+            inner: self.inner[..n.min(self.inner.len())].to_vec(),
+            offsets: OffsetCache::default(),
+        }
+    }
+
+    /// Adds an input at the position given by `offset` (after any existing
+    /// events at the same offset) and returns its index.
+    ///
+    /// The timestamps of the events are re-derived; errors (leaving the list
+    /// unchanged) if the new event would create a gap larger than
+    /// [`EventList::MAX_GAP_MILLIS`].
+    pub fn insert_event(
+        &mut self,
+        input: Input,
+        offset: Duration,
+    ) -> Result<usize, FromEventsError> {
+        let mut events: Vec<_> = self.iter().collect();
+        let index = events.partition_point(|&(_, o)| o <= offset);
+        events.insert(index, (input, offset));
+
+        *self = EventList::from_events(events)?;
+        Ok(index)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum TimeScaleError {
+    #[error("can't scale time by {factor}; the factor must be finite and non-negative")]
+    InvalidFactor { factor: f64 },
+    #[error("the scaled events can't be represented: {}", .0)]
+    Unrepresentable(#[from] FromEventsError),
+}
+
+impl EventList {
+    /// The offset of the last event from the start of the game.
+    pub fn total_duration(&self) -> Duration {
+        self.iter()
+            .last()
+            .map(|(_, offset)| offset)
+            .unwrap_or_else(Duration::zero)
+    }
+
+    /// Stretches (`factor > 1`) or squashes (`factor < 1`) the time between
+    /// events.
+    ///
+    /// Errors if any of the scaled gaps between events is larger than
+    /// [`EventList::MAX_GAP_MILLIS`]. Such gaps would need extra rollovers,
+    /// but the format has no rollover marker: a wrap is only detected by a
+    /// timestamp going backwards (see [`EventList::iter`]), so there's no
+    /// way to represent a gap of 4096ms or more. Inserting filler inputs
+    /// instead would change the game.
+    pub fn time_scale(&self, factor: f64) -> Result<EventList, TimeScaleError> {
+        if !factor.is_finite() || factor < 0. {
+            return Err(TimeScaleError::InvalidFactor { factor });
+        }
+
+        // Scaling offsets rather than individual gaps so rounding errors don't
+        // accumulate.
+        let scale = |d: Duration| {
+            Duration::milliseconds((d.num_milliseconds() as f64 * factor).round() as _)
+        };
+        Ok(EventList::from_events(
+            self.iter().map(|(input, offset)| (input, scale(offset))),
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod test_from_events {
+    use super::*;
+
+    #[test]
+    fn inverse_of_iter() {
+        let replay = test_util::sample();
+        assert_eq!(EventList::from_events(replay.data.iter()), Ok(replay.data));
+    }
+
+    #[test]
+    fn try_from_vec() {
+        let ms = Duration::milliseconds;
+        let events = vec![(Input::MoveLeft, ms(100)), (Input::HardDrop, ms(500))];
+
+        let list: EventList = events.clone().try_into().unwrap();
+        let decoded = EventList::try_from(list.encode()).unwrap();
+        assert_eq!(decoded.iter().take(2).collect::<Vec<_>>(), events);
+
+        let backwards = vec![(Input::HardDrop, ms(500)), (Input::MoveLeft, ms(100))];
+        assert_eq!(
+            EventList::try_from(backwards),
+            Err(FromEventsError::NotMonotonic { index: 1 })
+        );
+    }
+
+    #[test]
+    fn try_from_slice() {
+        let sample = test_util::sample().data;
+        let encoded = sample.encode();
+
+        // e.g. the events as part of a larger buffer:
+        let mut buffer = vec![0xAB; 3];
+        buffer.extend(&encoded);
+        buffer.push(0xCD);
+        let slice = &buffer[3..buffer.len() - 1];
+
+        assert_eq!(EventList::try_from(slice), Ok(sample.clone()));
+        assert_eq!(EventList::try_from(slice), EventList::try_from(encoded));
+        assert_eq!(
+            EventList::try_from(&slice[..6]),
+            Err(EventListParseError::NotAligned { num_bytes: 6 })
+        );
+    }
+
+    #[test]
+    fn decode_iter() {
+        let sample = test_util::sample().data;
+        let encoded = sample.encode();
+
+        let events: Vec<_> = EventList::decode_iter(&encoded)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(events, *sample);
+        assert_eq!(events[0].input(), sample.iter().next().unwrap().0);
+
+        // The events before a short chunk still come through:
+        let mut decoded = EventList::decode_iter(&encoded[..6]);
+        assert_eq!(decoded.next(), Some(Ok(sample[0])));
+        assert_eq!(decoded.next(), Some(Ok(sample[1])));
+        assert_eq!(
+            decoded.next(),
+            Some(Err(EventListParseError::NotAligned { num_bytes: 6 }))
+        );
+        assert_eq!(decoded.next(), None);
+
+        assert_eq!(EventList::decode_iter(&[]).next(), None);
+    }
+
+    #[test]
+    fn time_scale() {
+        let events: Vec<_> = (1..=10).map(|i| (Input::HardDrop, i * 300)).collect();
+        let replay = test_util::with_events(&events);
+        assert_eq!(replay.data.total_duration(), Duration::milliseconds(3000));
+
+        let doubled = replay.data.time_scale(2.0).unwrap();
+        assert_eq!(doubled.total_duration(), Duration::milliseconds(6000));
+        assert_eq!(doubled.len(), replay.data.len());
+
+        let halved = replay.time_scale(0.5).unwrap();
+        assert_eq!(halved.data.total_duration(), Duration::milliseconds(1500));
+        assert_eq!(halved.time(), replay.time() / 2);
+
+        assert!(matches!(
+            replay.data.time_scale(20.0),
+            Err(TimeScaleError::Unrepresentable(
+                FromEventsError::GapTooLarge { index: 0, .. }
+            )),
+        ));
+        assert!(matches!(
+            replay.data.time_scale(f64::NAN),
+            Err(TimeScaleError::InvalidFactor { .. }),
+        ));
+    }
+
+    #[test]
+    fn insert_event() {
+        let ms = Duration::milliseconds;
+        let mut list = test_util::with_events(&[
+            (Input::HardDrop, 1000),
+            (Input::HardDrop, 4000),
+            (Input::HardDrop, 5000),
+        ])
+        .data;
+
+        assert_eq!(list.insert_event(Input::HoldBlock, ms(4000)), Ok(2));
+        assert_eq!(list.insert_event(Input::MoveLeft, ms(4500)), Ok(3));
+        assert_eq!(list.insert_event(Input::MoveLeft, ms(0)), Ok(0));
+
+        let events: Vec<_> = list.iter().collect();
+        assert!(events.windows(2).all(|w| w[0].1 <= w[1].1));
+        assert_eq!(
+            events,
+            [
+                (Input::MoveLeft, ms(0)),
+                (Input::HardDrop, ms(1000)),
+                (Input::HardDrop, ms(4000)),
+                (Input::HoldBlock, ms(4000)),
+                (Input::MoveLeft, ms(4500)),
+                (Input::HardDrop, ms(5000)),
+            ]
+        );
+
+        let before = list.clone();
+        assert!(matches!(
+            list.insert_event(Input::HardDrop, ms(10_000)),
+            Err(FromEventsError::GapTooLarge { index: 6, .. })
+        ));
+        assert_eq!(list, before);
+    }
+
+    #[test]
+    fn truncate_to_events() {
+        let sample = test_util::sample().data;
+
+        let short = sample.truncate_to_events(10);
+        assert_eq!(short.len(), 10);
+        assert!(short.iter().eq(sample.iter().take(10)));
+        assert_eq!(
+            EventList::try_from(short.encode()).unwrap().iter().count(),
+            10
+        );
+
+        // Past a rollover:
+        let long = sample.truncate_to_events(150);
+        assert!(long.total_duration() > Duration::milliseconds(0x1000));
+        assert!(long.iter().eq(sample.iter().take(150)));
+
+        assert_eq!(sample.truncate_to_events(10_000), sample);
+    }
+
+    #[test]
+    fn append_event() {
+        let ms = Duration::milliseconds;
+        let mut list = EventList::from_events([]).unwrap();
+
+        assert_eq!(list.append_event(Input::HardDrop, ms(4000)), Ok(()));
+        assert_eq!(list.append_event(Input::HardDrop, ms(4200)), Ok(()));
+        assert_eq!(
+            list.append_event(Input::HardDrop, ms(4100)),
+            Err(FromEventsError::NotMonotonic { index: 2 })
+        );
+        assert_eq!(
+            list.iter().collect::<Vec<_>>(),
+            [(Input::HardDrop, ms(4000)), (Input::HardDrop, ms(4200))]
+        );
+    }
+
+    #[test]
+    fn longer_than_a_wrap() {
+        let ms = Duration::milliseconds;
+        // Gaps of exactly `MAX_GAP_MILLIS` (timestamp 0xFFF included), and
+        // inputs in the same millisecond on either side of a wrap:
+        let events = [
+            (Input::MoveLeft, ms(0)),
+            (Input::HardDrop, ms(4095)),
+            (Input::HardDrop, ms(4095)),
+            (Input::MoveRight, ms(4096)),
+            (Input::HardDrop, ms(8191)),
+            (Input::RotateLeft, ms(12_286)),
+            (Input::HardDrop, ms(12_290)),
+        ];
+
+        let list = EventList::from_events(events).unwrap();
+        let decoded = EventList::try_from(list.encode()).unwrap();
+        assert_eq!(
+            decoded.iter().take(events.len()).collect::<Vec<_>>(),
+            events
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let ms = Duration::milliseconds;
+
+        assert_eq!(
+            EventList::from_events([(Input::HardDrop, ms(10)), (Input::HardDrop, ms(5))]),
+            Err(FromEventsError::NotMonotonic { index: 1 }),
+        );
+        assert_eq!(
+            EventList::from_events([(Input::HardDrop, ms(10)), (Input::HardDrop, ms(4106))]),
+            Err(FromEventsError::GapTooLarge { index: 1, gap: ms(4096) }),
+        );
+        assert!(EventList::from_events([(Input::HardDrop, ms(10)), (Input::HardDrop, ms(4105))]).is_ok());
+    }
+}
+
+// impl AsRef<[u8]> for EventList {
+//     fn as_ref(&self) -> &[u8] {
+//         if let Some(inner) = *self._encoded.borrow() {
+//             inner
+//         } else {
+//             *self._encoded.borrow_mut() = Some(self.encode());
+//             self._encoded.borrow()
+//         }
+//     }
+// }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Event {
+    timestamp: TwelveBitMillisecondTimestamp,
+    input: Input,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Error)]
+pub enum EventDecodeError {
+    #[error("{raw} is not the code of an input")]
+    InvalidInput { raw: u8 },
+    #[error("invalid event timestamp: {}", .0)]
+    InvalidTimestamp(#[from] TwelveBitMillisecondTimestampConversionError),
+}
+
+impl Event {
+    /// The event's 12-bit timestamp; see [`EventList::iter`] for turning
+    /// these into offsets from the start of the game.
+    pub const fn timestamp(self) -> TwelveBitMillisecondTimestamp {
+        self.timestamp
+    }
+
+    pub const fn input(self) -> Input {
+        self.input
+    }
+}
+
+impl TryFrom<u16> for Event {
+    type Error = EventDecodeError;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        let delay = value >> 4;
+        let input = (value & 0x0F) as u8;
+
+        // Neither can actually fail (12 and 4 bits respectively) but there's
+        // no reason to rely on that here.
+        Ok(Event {
+            timestamp: delay.try_into()?,
+            input: Input::try_from(input).map_err(|InvalidInputCode { code }| {
+                EventDecodeError::InvalidInput { raw: code }
+            })?,
+        })
+    }
+}
+
+impl From<Event> for u16 {
+    fn from(ev: Event) -> u16 {
+        ev.timestamp.millis() << 4 | (ev.input as u16)
+    }
+}
+
+#[cfg(test)]
+mod test_event_words {
+    use super::*;
+
+    #[test]
+    fn every_word_decodes() {
+        for word in 0..=u16::MAX {
+            let event = Event::try_from(word).unwrap();
+            assert_eq!(u16::from(event), word);
+        }
+    }
+}
+
+/// An input (or game event) as recorded in a replay.
+///
+/// The discriminants are the codes the jstris client uses for its replay
+/// actions; each variant notes the name of the corresponding jstris constant
+/// (listed in the notes in `main.rs`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum Input {
+    /// `MOVE_LEFT`
+    MoveLeft = 0,
+    /// `MOVE_RIGHT`
+    MoveRight = 1,
+    /// `DAS_LEFT`
+    DasLeft = 2,
+    /// `DAS_RIGHT`
+    DasRight = 3,
+    /// `ROTATE_LEFT`
+    RotateLeft = 4,
+    /// `ROTATE_RIGHT`
+    RotateRight = 5,
+    /// `ROTATE_180`
+    Rotate180 = 6,
+    /// `HARD_DROP`
+    HardDrop = 7,
+    /// `SOFT_DROP_BEGIN_END`
+    SoftDropBeginEnd = 8,
+    /// `GRAVITY_STEP`
+    GravityStep = 9,
+    /// `HOLD_BLOCK`
+    HoldBlock = 10,
+    /// `GARBAGE_ADD`
+    GarbageAdd = 11,
+    /// `SGARBAGE_ADD`
+    SGarbageAdd = 12,
+    /// `REDBAR_SET`
+    RedBarSet = 13,
+    /// `ARR_MOVE`
+    ArrMove = 14,
+    /// `AUX`
+    Aux = 15,
+}
+
+impl Input {
+    /// The name of the input's constant in the jstris source, in kebab case:
+    /// `move-left`, `hard-drop`, `redbar-set`, etc.
+    ///
+    /// This is what [`Display`] prints and what [`FromStr`](std::str::FromStr)
+    /// accepts.
+    pub const fn name(self) -> &'static str {
+        use Input::*;
+
+        match self {
+            MoveLeft => "move-left",
+            MoveRight => "move-right",
+            DasLeft => "das-left",
+            DasRight => "das-right",
+            RotateLeft => "rotate-left",
+            RotateRight => "rotate-right",
+            Rotate180 => "rotate-180",
+            HardDrop => "hard-drop",
+            SoftDropBeginEnd => "soft-drop-begin-end",
+            GravityStep => "gravity-step",
+            HoldBlock => "hold-block",
+            GarbageAdd => "garbage-add",
+            SGarbageAdd => "sgarbage-add",
+            RedBarSet => "redbar-set",
+            ArrMove => "arr-move",
+            Aux => "aux",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Error)]
+#[error("{code} is not the code of an input")]
+pub struct InvalidInputCode {
+    pub code: u8,
+}
+
+impl TryFrom<u8> for Input {
+    type Error = InvalidInputCode;
+
+    fn try_from(code: u8) -> Result<Self, Self::Error> {
+        Input::from_code(code).ok_or(InvalidInputCode { code })
+    }
+}
+
+impl Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum InputParseError {
+    #[error("expected the name of an input; got an empty string")]
+    Empty,
+    #[error("`{name}` is not the name of an input")]
+    UnknownName { name: String },
+}
+
+impl std::str::FromStr for Input {
+    type Err = InputParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(InputParseError::Empty);
+        }
+
+        Input::ALL
+            .into_iter()
+            .find(|input| input.name() == s)
+            .ok_or_else(|| InputParseError::UnknownName {
+                name: s.to_string(),
+            })
+    }
+}
+
+#[cfg(test)]
+mod test_input_name {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for input in Input::ALL {
+            assert_eq!(input.to_string().parse(), Ok(input));
+        }
+        assert_eq!(Input::MoveLeft.to_string(), "move-left");
+        assert_eq!(Input::HardDrop.to_string(), "hard-drop");
+        assert_eq!(Input::Rotate180.to_string(), "rotate-180");
+    }
+
+    #[test]
+    fn unknown() {
+        assert_eq!("".parse::<Input>(), Err(InputParseError::Empty));
+        assert_eq!(
+            "MoveLeft".parse::<Input>(),
+            Err(InputParseError::UnknownName {
+                name: "MoveLeft".to_string()
+            })
+        );
+    }
+}
+
+impl Input {
+    /// Every input, in code order.
+    pub const ALL: [Input; 16] = {
+        use Input::*;
+
+        [
+            MoveLeft,
+            MoveRight,
+            DasLeft,
+            DasRight,
+            RotateLeft,
+            RotateRight,
+            Rotate180,
+            HardDrop,
+            SoftDropBeginEnd,
+            GravityStep,
+            HoldBlock,
+            GarbageAdd,
+            SGarbageAdd,
+            RedBarSet,
+            ArrMove,
+            Aux,
+        ]
+    };
+
+    /// The input with the given jstris code, if there is one.
+    pub const fn from_code(code: u8) -> Option<Self> {
+        use Input::*;
+
+        Some(match code {
+            0 => MoveLeft,
+            1 => MoveRight,
+            2 => DasLeft,
+            3 => DasRight,
+            4 => RotateLeft,
+            5 => RotateRight,
+            6 => Rotate180,
+            7 => HardDrop,
+            8 => SoftDropBeginEnd,
+            9 => GravityStep,
+            10 => HoldBlock,
+            11 => GarbageAdd,
+            12 => SGarbageAdd,
+            13 => RedBarSet,
+            14 => ArrMove,
+            15 => Aux,
+            _ => return None,
+        })
+    }
+
+    /// The input for the low 4 bits of `raw`; every 4 bit value is an input.
+    ///
+    /// `raw`'s upper bits are expected to be clear (this panics otherwise,
+    /// in debug builds). Prefer [`Input::from_code`] or `Input::try_from`,
+    /// which don't need that.
+    #[inline]
+    pub fn from_raw(raw: u8) -> Self {
+        debug_assert!(raw & 0xF0 == 0);
+
+        match Input::try_from(raw & 0x0F) {
+            Ok(input) => input,
+            Err(_) => unreachable!("every 4 bit value is an input"),
+        }
+    }
+
+    /// The jstris code for this input.
+    pub const fn code(self) -> u8 {
+        self as u8
+    }
+
+    /// Whether events with this input are followed by extra payload in the
+    /// event data, i.e. whether a reader walking the encoded events needs to
+    /// consume more than this input's own 2 byte word.
+    ///
+    /// The payloads are:
+    ///   - [`GarbageAdd`](Input::GarbageAdd) and
+    ///     [`SGarbageAdd`](Input::SGarbageAdd): one 2 byte word (presumably
+    ///     the hole column and the number of lines)
+    ///   - [`RedBarSet`](Input::RedBarSet): one 2 byte word (the height of the
+    ///     bar)
+    ///   - [`Aux`](Input::Aux): at least one 2 byte word whose low nibble is
+    ///     the [`AuxInput`]; how much more some kinds carry isn't known (see
+    ///     [`AuxInput::fits_in_one_word`])
+    ///
+    /// None of the replays we have contain these events so these sizes are
+    /// unverified.
+    // TODO: check these against the jstris client
+    pub const fn requires_payload(self) -> bool {
+        use Input::*;
+
+        matches!(self, GarbageAdd | SGarbageAdd | RedBarSet | Aux)
+    }
+
+    /// Whether this is [`Input::Aux`]; the kind of aux event is in its
+    /// payload (see [`EventList::iter_decoded`]).
+    pub const fn is_aux(self) -> bool {
+        matches!(self, Input::Aux)
+    }
+
+    /// Whether this is an event the game generated (gravity, garbage, etc.)
+    /// rather than something the player did.
+    pub const fn is_system(self) -> bool {
+        use Input::*;
+
+        matches!(
+            self,
+            GravityStep | GarbageAdd | SGarbageAdd | RedBarSet | Aux
+        )
+    }
+
+    /// The key bound to this input in jstris' default controls.
+    ///
+    /// `None` for [system events](Input::is_system) and for
+    /// [`ArrMove`](Input::ArrMove) (which is a repeat of whichever direction
+    /// is being held rather than a key press of its own).
+    pub const fn default_key_label(self) -> Option<&'static str> {
+        use Input::*;
+
+        Some(match self {
+            MoveLeft | DasLeft => "Left",
+            MoveRight | DasRight => "Right",
+            RotateLeft => "Z",
+            RotateRight => "Up",
+            Rotate180 => "A",
+            HardDrop => "Space",
+            SoftDropBeginEnd => "Down",
+            HoldBlock => "C",
+            ArrMove | GravityStep | GarbageAdd | SGarbageAdd | RedBarSet | Aux => return None,
+        })
+    }
+}
+
+impl AuxInput {
+    /// Every kind of aux event, in code order.
+    pub const ALL: [AuxInput; 6] = {
+        use AuxInput::*;
+
+        [Afk, BlockSet, MoveTo, Randomizer, MatrixMod, WideGarbageMod]
+    };
+
+    /// The jstris code for this kind of aux event.
+    pub const fn code(self) -> u8 {
+        self as u8
+    }
+
+    /// The kind of aux event with the given code, if there is one.
+    pub const fn from_code(code: u8) -> Option<AuxInput> {
+        use AuxInput::*;
+
+        Some(match code {
+            0 => Afk,
+            1 => BlockSet,
+            2 => MoveTo,
+            3 => Randomizer,
+            4 => MatrixMod,
+            5 => WideGarbageMod,
+            _ => return None,
+        })
+    }
+
+    /// Whether the whole event fits in the word that holds its kind (in the
+    /// other 12 bits), as opposed to needing more words whose number we don't
+    /// know.
+    ///
+    /// [`Afk`](AuxInput::Afk), [`Randomizer`](AuxInput::Randomizer) and
+    /// [`WideGarbageMod`](AuxInput::WideGarbageMod) are taken to carry a
+    /// single small value. The others (a block, a position, a change to the
+    /// matrix) presumably need more; since how much isn't known,
+    /// [`EventList::iter_decoded`] errors on them rather than guessing. None
+    /// of the replays we have contain aux events so this is unverified.
+    // TODO: check this against the jstris client
+    pub const fn fits_in_one_word(self) -> bool {
+        use AuxInput::*;
+
+        matches!(self, Afk | Randomizer | WideGarbageMod)
+    }
+}
+
+// `Input::from_raw` relies on the inputs' codes being exactly `0..16`; check
+// that `ALL` (and `Input::from_code`) list a variant for each code, in order.
+// The `match`es stop compiling if a variant is added (at which point `ALL`,
+// `Input::from_code` and these checks need updating too).
+const _: () = {
+    const fn exhaustive(input: Input, aux: AuxInput) {
+        use AuxInput::*;
+        use Input::*;
+
+        match input {
+            MoveLeft | MoveRight | DasLeft | DasRight | RotateLeft | RotateRight | Rotate180
+            | HardDrop | SoftDropBeginEnd | GravityStep | HoldBlock | GarbageAdd | SGarbageAdd
+            | RedBarSet | ArrMove | Aux => {}
+        }
+        match aux {
+            Afk | BlockSet | MoveTo | Randomizer | MatrixMod | WideGarbageMod => {}
+        }
+    }
+    let _ = exhaustive;
+
+    let mut i = 0;
+    while i < Input::ALL.len() {
+        assert!(Input::ALL[i].code() as usize == i);
+        assert!(matches!(Input::from_code(i as u8), Some(input) if input as usize == i));
+        i += 1;
+    }
+    assert!(Input::ALL.len() == 16);
+    assert!(Input::from_code(16).is_none());
+
+    let mut i = 0;
+    while i < AuxInput::ALL.len() {
+        assert!(AuxInput::ALL[i].code() as usize == i);
+        i += 1;
+    }
+    assert!(AuxInput::ALL.len() == 6);
+};
+
+#[cfg(test)]
+mod test_input_codes {
+    use super::*;
+
+    #[test]
+    fn input() {
+        use Input::*;
+
+        let expected = [
+            (MoveLeft, 0),
+            (MoveRight, 1),
+            (DasLeft, 2),
+            (DasRight, 3),
+            (RotateLeft, 4),
+            (RotateRight, 5),
+            (Rotate180, 6),
+            (HardDrop, 7),
+            (SoftDropBeginEnd, 8),
+            (GravityStep, 9),
+            (HoldBlock, 10),
+            (GarbageAdd, 11),
+            (SGarbageAdd, 12),
+            (RedBarSet, 13),
+            (ArrMove, 14),
+            (Aux, 15),
+        ];
+        for (input, code) in expected {
+            assert_eq!(input.code(), code, "{input:?}");
+            assert_eq!(Input::from_raw(code), input);
+        }
+    }
+
+    #[test]
+    fn requires_payload() {
+        use Input::*;
+
+        let expected = [
+            (MoveLeft, false),
+            (MoveRight, false),
+            (DasLeft, false),
+            (DasRight, false),
+            (RotateLeft, false),
+            (RotateRight, false),
+            (Rotate180, false),
+            (HardDrop, false),
+            (SoftDropBeginEnd, false),
+            (GravityStep, false),
+            (HoldBlock, false),
+            (GarbageAdd, true),
+            (SGarbageAdd, true),
+            (RedBarSet, true),
+            (ArrMove, false),
+            (Aux, true),
+        ];
+        for (input, payload) in expected {
+            assert_eq!(input.requires_payload(), payload, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn aux() {
+        use AuxInput::*;
+
+        let expected = [
+            (Afk, 0),
+            (BlockSet, 1),
+            (MoveTo, 2),
+            (Randomizer, 3),
+            (MatrixMod, 4),
+            (WideGarbageMod, 5),
+        ];
+        for (aux, code) in expected {
+            assert_eq!(aux.code(), code, "{aux:?}");
+        }
+    }
+
+    #[test]
+    fn from_code() {
+        for code in 0..16 {
+            assert_eq!(Input::from_code(code), Some(Input::from_raw(code)));
+        }
+        assert_eq!(Input::from_code(16), None);
+        assert_eq!(Input::from_code(0xF0), None);
+    }
+
+    #[test]
+    fn try_from_every_byte() {
+        for code in 0..=u8::MAX {
+            match Input::try_from(code) {
+                Ok(input) => assert_eq!(input.code(), code),
+                Err(err) => {
+                    assert!(code >= 16, "{code}");
+                    assert_eq!(err, InvalidInputCode { code });
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn all() {
+        assert_eq!(Input::ALL.len(), 16);
+        for (code, input) in Input::ALL.into_iter().enumerate() {
+            assert_eq!(Input::from_raw(code as u8), input);
+        }
+
+        assert_eq!(AuxInput::ALL.len(), 6);
+        let mut codes: Vec<_> = AuxInput::ALL.iter().map(|a| a.code()).collect();
+        codes.dedup();
+        assert_eq!(codes, [0, 1, 2, 3, 4, 5]);
+    }
+}
+
+/// The kinds of [`Input::Aux`] events.
+///
+/// Like [`Input`], the discriminants are the jstris client's codes and each
+/// variant notes the name of the corresponding jstris constant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum AuxInput {
+    /// `AFK`
+    Afk = 0,
+    /// `BLOCK_SET`
+    BlockSet = 1,
+    /// `MOVE_TO`
+    MoveTo = 2,
+    /// `RANDOMIZER`
+    Randomizer = 3,
+    /// `MATRIX_MOD`
+    MatrixMod = 4,
+    /// `WIDE_GARBAGE_ADD`
+    WideGarbageMod = 5,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TwelveBitMillisecondTimestamp(u16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Error)]
+pub enum TwelveBitMillisecondTimestampConversionError<Source: Display = u16> {
+    #[error("timestamp {value} exceeds 12-bit range (max {})", TwelveBitMillisecondTimestamp::MAX.millis())]
+    TooBig { value: Source },
+    #[error("timestamp {value} is not a valid 12-bit timestamp")]
+    Invalid { value: Source },
+}
+
+impl TryFrom<Duration> for TwelveBitMillisecondTimestamp {
+    type Error = TwelveBitMillisecondTimestampConversionError<Duration>;
+
+    fn try_from(duration: Duration) -> Result<Self, Self::Error> {
+        use TwelveBitMillisecondTimestampConversionError as E;
+
+        match duration.num_milliseconds() {
+            val @ 0..=0x0F_FF => Ok(Self(val as u16)),
+            val if val >= 0 => Err(E::TooBig { value: duration }),
+            _ => Err(E::Invalid { value: duration }),
+        }
+    }
+}
+
+impl From<TwelveBitMillisecondTimestamp> for Duration {
+    fn from(timestamp: TwelveBitMillisecondTimestamp) -> Duration {
+        Duration::milliseconds(timestamp.0 as _)
+    }
+}
+
+impl TryFrom<u16> for TwelveBitMillisecondTimestamp {
+    type Error = TwelveBitMillisecondTimestampConversionError;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        Self::from_millis(value)
+    }
+}
+
+impl TwelveBitMillisecondTimestamp {
+    pub const MAX: Self = Self(0x0F_FF);
+
+    /// Errors if `millis` doesn't fit in 12 bits (i.e. is over [`Self::MAX`]).
+    pub const fn from_millis(
+        millis: u16,
+    ) -> Result<Self, TwelveBitMillisecondTimestampConversionError> {
+        use TwelveBitMillisecondTimestampConversionError as E;
+
+        match millis {
+            0..=0x0F_FF => Ok(Self(millis)),
+            _ => Err(E::TooBig { value: millis }),
+        }
+    }
+
+    pub const fn millis(self) -> u16 {
+        self.0
+    }
+
+    /// Whether `duration` is in the range of 12-bit timestamps, i.e. in
+    /// `[0, MAX]` (ignoring any sub-millisecond part).
+    pub fn fits(duration: Duration) -> bool {
+        duration >= Duration::zero() && duration.num_milliseconds() <= Self::MAX.0 as i64
+    }
+}
+
+impl PartialEq<Duration> for TwelveBitMillisecondTimestamp {
+    fn eq(&self, other: &Duration) -> bool {
+        Duration::from(*self) == *other
+    }
+}
+
+impl PartialOrd<Duration> for TwelveBitMillisecondTimestamp {
+    fn partial_cmp(&self, other: &Duration) -> Option<std::cmp::Ordering> {
+        Duration::from(*self).partial_cmp(other)
+    }
+}
+
+#[cfg(test)]
+mod test_timestamp {
+    use super::*;
+
+    #[test]
+    fn too_big_message() {
+        let err = TwelveBitMillisecondTimestamp::try_from(0x1000u16).unwrap_err();
+        assert_eq!(
+            err,
+            TwelveBitMillisecondTimestampConversionError::TooBig { value: 4096 }
+        );
+        assert_eq!(
+            err.to_string(),
+            "timestamp 4096 exceeds 12-bit range (max 4095)"
+        );
+    }
+
+    #[test]
+    fn boundaries() {
+        let ms = Duration::milliseconds;
+        type T = TwelveBitMillisecondTimestamp;
+        use TwelveBitMillisecondTimestampConversionError as E;
+
+        assert_eq!(T::from_millis(0), Ok(TwelveBitMillisecondTimestamp(0)));
+        assert_eq!(T::from_millis(4095), Ok(T::MAX));
+        assert_eq!(T::from_millis(4096), Err(E::TooBig { value: 4096 }));
+
+        // Zero is a valid delay (e.g. for inputs in the same tick):
+        assert_eq!(T::try_from(ms(0)), Ok(TwelveBitMillisecondTimestamp(0)));
+        assert_eq!(T::try_from(ms(4095)), Ok(T::MAX));
+        assert_eq!(T::try_from(ms(4096)), Err(E::TooBig { value: ms(4096) }));
+        assert_eq!(T::try_from(ms(-1)), Err(E::Invalid { value: ms(-1) }));
+    }
+
+    #[test]
+    fn fits() {
+        let ms = Duration::milliseconds;
+
+        assert!(TwelveBitMillisecondTimestamp::fits(ms(0)));
+        assert!(TwelveBitMillisecondTimestamp::fits(ms(4095)));
+        assert!(!TwelveBitMillisecondTimestamp::fits(ms(4096)));
+        assert!(!TwelveBitMillisecondTimestamp::fits(ms(-1)));
+    }
+
+    #[test]
+    fn compare_with_duration() {
+        let ms = Duration::milliseconds;
+        let max = TwelveBitMillisecondTimestamp::MAX;
+
+        assert_eq!(max, ms(4095));
+        assert_ne!(max, ms(4094));
+        assert!(max < ms(4096));
+        assert!(max > ms(-1));
+        assert!(TwelveBitMillisecondTimestamp(30) <= ms(30));
+    }
+}
+
+impl Display for TwelveBitMillisecondTimestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<Duration>::into(*self))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct Metadata {
+    #[serde(rename = "softDropId")]
+    pub soft_drop_id: SoftDropSpeed,
+
+    /// Milliseconds since the Unix epoch on the wire; see
+    /// [`ts_milliseconds_or_seconds`] for replays that use seconds.
+    #[serde(rename = "gameStart")]
+    #[serde(with = "ts_milliseconds_or_seconds")]
+    pub game_start: DateTime<Utc>,
+    #[serde(rename = "gameEnd")]
+    #[serde(with = "ts_milliseconds_or_seconds")]
+    pub game_end: DateTime<Utc>,
+
+    pub seed: GameSeed,
+
+    #[serde(rename = "bs")]
+    pub block_skin: BlockSkin,
+
+    #[serde(rename = "se")]
+    pub sound_effects: SoundEffects,
+
+    /// Delayed Auto Shift, in milliseconds; `None` if the replay doesn't
+    /// specify it.
+    ///
+    /// See [here](https://tetris.wiki/DAS).
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_handling_millis"
+    )]
+    pub das: Option<u16>, // jstris allows [0, 4999]
+
+    /// Auto Repeat Rate, in milliseconds; `None` if the replay doesn't
+    /// specify it.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_handling_millis"
+    )]
+    pub arr: Option<u16>, // jstris allows [0, 4999]
+
+    #[serde(rename = "m")]
+    pub game_mode: GameMode, // ???
+
+    #[serde(rename = "v")]
+    pub version: ExpectedJstrisReplayVersion<3, 0>, // we're compatible with 3.0 and up (tested through 3.3)
+
+    /// Absent in some replays; this is kept that way when re-encoding.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub r: Option<UnknownR>,
+
+    /// Absent in some replays; see [`Metadata::big_blocks`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bbs: Option<BigBlocks>,
+}
+
+impl Metadata {
+    /// Whether the game was played with big blocks; `false` if the replay
+    /// doesn't say.
+    pub fn big_blocks(&self) -> bool {
+        self.bbs.is_some_and(BigBlocks::is_big)
+    }
+}
+
+impl Metadata {
+    /// Metadata for a fresh game of `mode` that starts (and ends) now, with
+    /// zero DAS/ARR, instant soft drop, the default skin and sounds, and a
+    /// random seed.
+    ///
+    /// Meant for prototyping replays; set `game_end` once the events are in.
+    pub fn default_for_mode(mode: GameMode) -> Metadata {
+        // Replays only store milliseconds.
+        let now = Utc::now();
+        let now = now - Duration::nanoseconds(now.timestamp_subsec_nanos() as i64 % 1_000_000);
+
+        Metadata {
+            soft_drop_id: SoftDropSpeed::Instant,
+            game_start: now,
+            game_end: now,
+            seed: GameSeed::random(),
+            block_skin: BlockSkin::default(),
+            sound_effects: SoundEffects::default(),
+            das: Some(0),
+            arr: Some(0),
+            game_mode: mode,
+            version: ExpectedJstrisReplayVersion::new(),
+            r: None,
+            bbs: None,
+        }
+    }
+
+    /// [`das`](Metadata::das) in frames at `fps` frames per second; this is
+    /// not rounded (use `.ceil()` for the number of whole frames before the
+    /// first auto shift). An unspecified DAS counts as 0.
+    pub fn das_frames(&self, fps: u32) -> f64 {
+        ms_to_frames(self.das.unwrap_or(0), fps)
+    }
+
+    /// [`arr`](Metadata::arr) in frames at `fps` frames per second; like
+    /// [`Metadata::das_frames`] this is not rounded and an unspecified ARR
+    /// counts as 0.
+    pub fn arr_frames(&self, fps: u32) -> f64 {
+        ms_to_frames(self.arr.unwrap_or(0), fps)
+    }
+}
+
+fn ms_to_frames(ms: u16, fps: u32) -> f64 {
+    ms as f64 * fps as f64 / 1000.
+}
+
+#[cfg(test)]
+mod test_handling_frames {
+    use super::*;
+
+    #[test]
+    fn at_60fps() {
+        let with = |das, arr| Metadata {
+            das,
+            arr,
+            ..test_util::sample().metadata
+        };
+
+        let m = with(Some(100), Some(0));
+        assert_eq!(m.das_frames(60), 6.);
+        assert_eq!(m.arr_frames(60), 0.);
+
+        let m = with(Some(83), Some(33));
+        assert!((m.das_frames(60) - 4.98).abs() < 1e-9);
+        assert!((m.arr_frames(60) - 1.98).abs() < 1e-9);
+
+        let m = with(None, None);
+        assert_eq!((m.das_frames(60), m.arr_frames(60)), (0., 0.));
+    }
+}
+
+#[cfg(test)]
+mod test_metadata_default {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let metadata = Metadata::default_for_mode(GameMode::_20Line);
+        assert_eq!(metadata.game_mode, GameMode::_20Line);
+        assert_eq!((metadata.das, metadata.arr), (Some(0), Some(0)));
+
+        let json = serde_json::to_string(&metadata).unwrap();
+        assert_eq!(serde_json::from_str::<Metadata>(&json).unwrap(), metadata);
+    }
+
+    #[test]
+    fn seeds_differ() {
+        let a = Metadata::default_for_mode(GameMode::_40Line).seed;
+        let b = Metadata::default_for_mode(GameMode::_40Line).seed;
+        assert_ne!(a, b);
+    }
+}
+
+impl Metadata {
+    /// The largest DAS or ARR (in milliseconds) jstris lets you pick.
+    pub const MAX_HANDLING_MILLIS: u16 = 4999;
+
+    /// A [`MetadataBuilder`]; `game_start`, `game_end`, `seed`, and
+    /// `game_mode` have to be set before building.
+    pub fn builder() -> MetadataBuilder {
+        MetadataBuilder {
+            soft_drop_id: SoftDropSpeed::Instant,
+            game_start: None,
+            game_end: None,
+            seed: None,
+            block_skin: BlockSkin::default(),
+            sound_effects: SoundEffects::default(),
+            das: 0,
+            arr: 0,
+            game_mode: None,
+            r: None,
+            bbs: None,
+        }
+    }
+}
+
+/// Builds a [`Metadata`] without having to spell out every field; see
+/// [`Metadata::builder`].
+///
+/// Everything that isn't required defaults to what
+/// [`Metadata::default_for_mode`] uses, and [`MetadataBuilder::build`] checks
+/// the result is something jstris could have produced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataBuilder {
+    soft_drop_id: SoftDropSpeed,
+    game_start: Option<DateTime<Utc>>,
+    game_end: Option<DateTime<Utc>>,
+    seed: Option<GameSeed>,
+    block_skin: BlockSkin,
+    sound_effects: SoundEffects,
+    das: u16,
+    arr: u16,
+    game_mode: Option<GameMode>,
+    r: Option<UnknownR>,
+    bbs: Option<BigBlocks>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Error)]
+pub enum MetadataBuildError {
+    #[error("`{field}` must be set")]
+    Missing { field: &'static str },
+    #[error("the game ends ({game_end}) before it starts ({game_start})")]
+    EndsBeforeStart {
+        game_start: DateTime<Utc>,
+        game_end: DateTime<Utc>,
+    },
+    #[error(
+        "a DAS of {das}ms is out of range (max {}ms)",
+        Metadata::MAX_HANDLING_MILLIS
+    )]
+    DasOutOfRange { das: u16 },
+    #[error(
+        "an ARR of {arr}ms is out of range (max {}ms)",
+        Metadata::MAX_HANDLING_MILLIS
+    )]
+    ArrOutOfRange { arr: u16 },
+}
+
+impl MetadataBuilder {
+    pub fn game_start(mut self, game_start: DateTime<Utc>) -> Self {
+        self.game_start = Some(game_start);
+        self
+    }
+
+    pub fn game_end(mut self, game_end: DateTime<Utc>) -> Self {
+        self.game_end = Some(game_end);
+        self
+    }
+
+    pub fn seed(mut self, seed: GameSeed) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn game_mode(mut self, game_mode: GameMode) -> Self {
+        self.game_mode = Some(game_mode);
+        self
+    }
+
+    /// Defaults to [`SoftDropSpeed::Instant`].
+    pub fn soft_drop_id(mut self, soft_drop_id: SoftDropSpeed) -> Self {
+        self.soft_drop_id = soft_drop_id;
+        self
+    }
+
+    pub fn block_skin(mut self, block_skin: BlockSkin) -> Self {
+        self.block_skin = block_skin;
+        self
+    }
+
+    pub fn sound_effects(mut self, sound_effects: SoundEffects) -> Self {
+        self.sound_effects = sound_effects;
+        self
+    }
+
+    /// In milliseconds; defaults to 0.
+    pub fn das(mut self, das: u16) -> Self {
+        self.das = das;
+        self
+    }
+
+    /// In milliseconds; defaults to 0.
+    pub fn arr(mut self, arr: u16) -> Self {
+        self.arr = arr;
+        self
+    }
+
+    /// Defaults to leaving `r` out.
+    pub fn r(mut self, r: UnknownR) -> Self {
+        self.r = Some(r);
+        self
+    }
+
+    /// Defaults to leaving `bbs` out (i.e. no big blocks).
+    pub fn bbs(mut self, bbs: BigBlocks) -> Self {
+        self.bbs = Some(bbs);
+        self
+    }
+
+    pub fn build(self) -> Result<Metadata, MetadataBuildError> {
+        use MetadataBuildError as E;
+
+        let game_start = self.game_start.ok_or(E::Missing {
+            field: "game_start",
+        })?;
+        let game_end = self.game_end.ok_or(E::Missing { field: "game_end" })?;
+        let seed = self.seed.ok_or(E::Missing { field: "seed" })?;
+        let game_mode = self.game_mode.ok_or(E::Missing { field: "game_mode" })?;
+
+        if game_end < game_start {
+            return Err(E::EndsBeforeStart {
+                game_start,
+                game_end,
+            });
+        }
+        if self.das > Metadata::MAX_HANDLING_MILLIS {
+            return Err(E::DasOutOfRange { das: self.das });
+        }
+        if self.arr > Metadata::MAX_HANDLING_MILLIS {
+            return Err(E::ArrOutOfRange { arr: self.arr });
+        }
+
+        Ok(Metadata {
+            soft_drop_id: self.soft_drop_id,
+            game_start,
+            game_end,
+            seed,
+            block_skin: self.block_skin,
+            sound_effects: self.sound_effects,
+            das: Some(self.das),
+            arr: Some(self.arr),
+            game_mode,
+            version: ExpectedJstrisReplayVersion::new(),
+            r: self.r,
+            bbs: self.bbs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test_metadata_builder {
+    use super::*;
+
+    fn required() -> MetadataBuilder {
+        let sample = test_util::sample().metadata;
+        Metadata::builder()
+            .game_start(sample.game_start)
+            .game_end(sample.game_end)
+            .seed(sample.seed)
+            .game_mode(GameMode::_40Line)
+    }
+
+    #[test]
+    fn defaults() {
+        let metadata = required().build().unwrap();
+        let sample = test_util::sample().metadata;
+
+        assert_eq!(
+            metadata,
+            Metadata {
+                game_start: sample.game_start,
+                game_end: sample.game_end,
+                seed: sample.seed,
+                ..Metadata::default_for_mode(GameMode::_40Line)
+            }
+        );
+    }
+
+    #[test]
+    fn everything() {
+        let sample = test_util::sample().metadata;
+        let metadata = required()
+            .game_mode(sample.game_mode)
+            .soft_drop_id(sample.soft_drop_id)
+            .block_skin(sample.block_skin)
+            .sound_effects(sample.sound_effects)
+            .das(sample.das.unwrap())
+            .r(sample.r.unwrap())
+            .build()
+            .unwrap();
+
+        // The builder always sets ARR; the sample doesn't have it.
+        assert_eq!(
+            metadata,
+            Metadata {
+                arr: Some(0),
+                ..sample
+            }
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        use MetadataBuildError as E;
+        let start = test_util::sample().metadata.game_start;
+
+        assert_eq!(
+            Metadata::builder().build(),
+            Err(E::Missing {
+                field: "game_start"
+            })
+        );
+        assert_eq!(
+            required()
+                .game_end(start - Duration::milliseconds(1))
+                .build(),
+            Err(E::EndsBeforeStart {
+                game_start: start,
+                game_end: start - Duration::milliseconds(1),
+            })
+        );
+        assert!(required().game_end(start).build().is_ok());
+
+        assert!(required().das(4999).arr(4999).build().is_ok());
+        assert_eq!(
+            required().das(5000).build(),
+            Err(E::DasOutOfRange { das: 5000 })
+        );
+        assert_eq!(
+            required().arr(5000).build(),
+            Err(E::ArrOutOfRange { arr: 5000 })
+        );
+    }
+}
+
+/// For [`Metadata::das`] and [`Metadata::arr`]: rejects values that jstris
+/// wouldn't allow (more than [`Metadata::MAX_HANDLING_MILLIS`]).
+fn deserialize_handling_millis<'de, D>(deserializer: D) -> Result<Option<u16>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    match Option::<u16>::deserialize(deserializer)? {
+        Some(millis) if millis > Metadata::MAX_HANDLING_MILLIS => Err(D::Error::invalid_value(
+            serde::de::Unexpected::Unsigned(millis as u64),
+            &"a DAS/ARR of at most 4999ms",
+        )),
+        millis => Ok(millis),
+    }
+}
+
+#[cfg(test)]
+mod test_handling_range {
+    use super::*;
+
+    fn with(handling: &str) -> Result<Metadata, DecodeError> {
+        decode_metadata_only(test_util::SAMPLE_JSON.replace(r#","das":83"#, handling))
+    }
+
+    #[test]
+    fn in_range() {
+        let metadata = with(r#","das":4999,"arr":0"#).unwrap();
+        assert_eq!((metadata.das, metadata.arr), (Some(4999), Some(0)));
+
+        // Absent is still fine:
+        let metadata = with("").unwrap();
+        assert_eq!((metadata.das, metadata.arr), (None, None));
+    }
+
+    #[test]
+    fn out_of_range() {
+        for handling in [r#","das":5000"#, r#","das":65535"#, r#","arr":5000"#] {
+            let Err(DecodeError::JsonDecodeError(err)) = with(handling) else {
+                panic!("{handling} should be rejected");
+            };
+            assert!(err.to_string().contains("at most 4999ms"), "{err}");
+        }
+    }
+}
+
+/// `ts_milliseconds`, except that timestamps that are implausibly small as
+/// milliseconds (i.e. before [`SECONDS_THRESHOLD`]) are read as seconds
+/// instead; some old or hand edited replays use seconds (possibly with a
+/// fractional part).
+///
+/// Timestamps are always written as milliseconds.
+///
+/// [`SECONDS_THRESHOLD`]: ts_milliseconds_or_seconds::SECONDS_THRESHOLD
+pub mod ts_milliseconds_or_seconds {
+    use chrono::{serde::ts_milliseconds, DateTime, TimeZone, Utc};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    /// `1e11`: as milliseconds this is in early 1973 (jstris is from 2014);
+    /// as seconds it's far in the future.
+    pub const SECONDS_THRESHOLD: f64 = 1e11;
+
+    pub fn serialize<S: Serializer>(dt: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+        ts_milliseconds::serialize(dt, serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime<Utc>, D::Error> {
+        let ts = f64::deserialize(deserializer)?;
+        if !ts.is_finite() || ts < 0. {
+            return Err(D::Error::custom(format!("{ts} is not a valid timestamp")));
+        }
+
+        let millis = if ts < SECONDS_THRESHOLD {
+            ts * 1000.
+        } else {
+            ts
+        };
+        Utc.timestamp_millis_opt(millis.round() as i64)
+            .single()
+            .ok_or_else(|| D::Error::custom(format!("timestamp {ts} is out of range")))
+    }
+}
+
+#[cfg(test)]
+mod test_metadata_timestamps {
+    use super::*;
+
+    fn start(game_start: &str) -> Result<DateTime<Utc>, serde_json::Error> {
+        let json = test_util::SAMPLE_JSON.replace("1684543650931", game_start);
+        decode_metadata_only(json)
+            .map(|m| m.game_start)
+            .map_err(|e| match e {
+                DecodeError::JsonDecodeError(e) => e,
+                e => panic!("{e}"),
+            })
+    }
+
+    #[test]
+    fn milliseconds() {
+        let expected = test_util::sample().metadata.game_start;
+        assert_eq!(expected.timestamp_millis(), 1684543650931);
+        assert_eq!(start("1684543650931").unwrap(), expected);
+        assert_eq!(start("1684543650931.0").unwrap(), expected);
+    }
+
+    #[test]
+    fn seconds() {
+        let expected = test_util::sample().metadata.game_start;
+        assert_eq!(start("1684543650.931").unwrap(), expected);
+        assert_eq!(
+            start("1684543650").unwrap(),
+            expected - Duration::milliseconds(931)
+        );
+
+        // Still written as milliseconds:
+        let json = serde_json::to_string(&test_util::sample().metadata).unwrap();
+        assert!(json.contains(r#""gameStart":1684543650931"#), "{json}");
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(start("-5").is_err());
+        assert!(start(r#""soon""#).is_err());
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ExpectedJstrisReplayVersion<const MAJOR: u8 = 3, const MINOR: u8 = 3> {
+    actual_minor: u8,
+}
+
+impl<const MAJ: u8, const MIN: u8> Debug for ExpectedJstrisReplayVersion<MAJ, MIN> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JstrisReplayVersion")
+            .field("major_ver", &MAJ)
+            .field("minor_ver", &self.actual_minor)
+            .finish()
+    }
+}
+
+impl<const MAJ: u8, const MIN: u8> Display for ExpectedJstrisReplayVersion<MAJ, MIN> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{MAJ}.{}", self.actual_minor)
+    }
+}
+
+impl<const MIN: u8> Default for ExpectedJstrisReplayVersion<3, MIN> {
+    fn default() -> Self {
         Self::new()
     }
 }
@@ -411,7 +2748,7 @@ impl<const MAJ: u8, const MIN: u8> Serialize for ExpectedJstrisReplayVersion<MAJ
         S: serde::Serializer,
     {
         let maj = MAJ as f32;
-        let mut min = MIN as f32;
+        let mut min = self.actual_minor as f32;
         while min >= 1. {
             min /= 10.;
         }
@@ -426,24 +2763,14 @@ impl<'de, const MAJ: u8, const MIN: u8> Deserialize<'de> for ExpectedJstrisRepla
         D: serde::Deserializer<'de>,
     {
         let ver = f32::deserialize(deserializer)?;
-
-        // bleh
-        let ver = format!("{ver}");
-        let (maj, min) = if let Some(pair) = ver.split_once('.') {
-            pair
-        } else {
-            (&*ver, "0")
-        };
-
-        let maj: u8 = maj.parse().map_err(D::Error::custom)?;
-        let min: u8 = min.parse().map_err(D::Error::custom)?;
+        let (maj, min) = parse_version(ver).map_err(D::Error::custom)?;
 
         if maj != MAJ {
             return Err(<D::Error as serde::de::Error>::custom(format!(
                 "expected major version {MAJ}, got major version {maj} in version number `{ver}`"
             )));
         }
-        if !(min >= MIN) {
+        if min < MIN {
             return Err(<D::Error as serde::de::Error>::custom(format!(
                 "expected minor version {MIN}, got minor version {min} in version number `{ver}`"
             )));
@@ -453,11 +2780,58 @@ impl<'de, const MAJ: u8, const MIN: u8> Deserialize<'de> for ExpectedJstrisRepla
     }
 }
 
+#[cfg(test)]
+mod test_version {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for minor in 0..=3 {
+            let metadata = Metadata {
+                version: ExpectedJstrisReplayVersion {
+                    actual_minor: minor,
+                },
+                ..test_util::sample().metadata
+            };
+
+            let json = serde_json::to_string(&metadata).unwrap();
+            assert!(json.contains(&format!(r#""v":3.{minor}"#)), "{json}");
+
+            let decoded: Metadata = serde_json::from_str(&json).unwrap();
+            assert_eq!(decoded.version.version(), (3, minor), "{json}");
+        }
+    }
+}
+
+/// Splits a version number like `3.3` into its major and minor parts.
+fn parse_version(ver: f32) -> Result<(u8, u8), std::num::ParseIntError> {
+    // bleh
+    let ver = format!("{ver}");
+    let (maj, min) = if let Some(pair) = ver.split_once('.') {
+        pair
+    } else {
+        (&*ver, "0")
+    };
+
+    Ok((maj.parse()?, min.parse()?))
+}
+
 #[derive(
-    Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize_repr, Deserialize_repr,
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize_repr,
+    Deserialize_repr,
+    Default,
 )]
 #[repr(u16)]
 pub enum BlockSkin {
+    #[default]
     SolidColor = 0,
     // Invisible = 1,  // not exposed via replay
     // Monochrome = 2, // not exposed via replay
@@ -482,69 +2856,383 @@ pub enum BlockSkin {
     /// https://s.jezevec10.com/res/b13.png
     Cats = 13,
 }
-// TODO: missing numbers in the above?
-// TODO: inline images above!
+// TODO: missing numbers in the above?
+// TODO: inline images above!
+
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize_repr,
+    Deserialize_repr,
+    Default,
+)]
+#[repr(u8)]
+pub enum SoundEffects {
+    Nullpomino = 0,
+    Yotipo = 1,
+    Rainforest = 2,
+    TetraX = 3,
+    #[default]
+    None = 4,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SoftDropSpeed {
+    Slow,
+    Medium,
+    Fast,
+    Ultra,
+    Instant,
+    /// A speed we don't know about; holds the raw id so that it survives a
+    /// round trip.
+    Other(u8),
+}
+
+impl SoftDropSpeed {
+    pub const fn from_id(id: u8) -> Self {
+        use SoftDropSpeed::*;
+
+        match id {
+            0 => Slow,
+            1 => Medium,
+            2 => Fast,
+            3 => Ultra,
+            4 => Instant,
+            other => Other(other),
+        }
+    }
+
+    /// The speed's id, as it appears in replays.
+    pub const fn id(self) -> u8 {
+        use SoftDropSpeed::*;
+
+        match self {
+            Slow => 0,
+            Medium => 1,
+            Fast => 2,
+            Ultra => 3,
+            Instant => 4,
+            Other(id) => id,
+        }
+    }
+
+    /// Unknown speeds are treated like the slowest ones (no extra steps).
+    // https://harddrop.com/forums/index.php?showtopic=7087&st=135&p=92057&#entry92057
+    pub const fn steps(self) -> u8 {
+        use SoftDropSpeed::*;
+
+        match self {
+            Slow | Medium | Other(_) => 0,
+            Fast => 1,
+            Ultra => 2,
+            Instant => 20,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SoftDropSpeed {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        u8::deserialize(deserializer).map(SoftDropSpeed::from_id)
+    }
+}
+
+impl Serialize for SoftDropSpeed {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_u8(self.id())
+    }
+}
+
+#[cfg(test)]
+mod test_soft_drop_speed {
+    use super::*;
+
+    #[test]
+    fn ids() {
+        for id in 0..=u8::MAX {
+            assert_eq!(SoftDropSpeed::from_id(id).id(), id);
+        }
+        assert_eq!(SoftDropSpeed::from_id(4), SoftDropSpeed::Instant);
+    }
+
+    #[test]
+    fn unknown_speed() {
+        let json = test_util::SAMPLE_JSON.replace(r#""softDropId":4"#, r#""softDropId":5"#);
+        let replay = decode_json(json).unwrap();
+
+        assert_eq!(replay.metadata.soft_drop_id, SoftDropSpeed::Other(5));
+        assert_eq!(replay.metadata.soft_drop_id.steps(), 0);
+
+        let reencoded = decode_uri_string(encode_uri_string(&replay).unwrap()).unwrap();
+        assert_eq!(reencoded, replay);
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GameMode {
+    // See: https://harddrop.com/forums/index.php?showtopic=7087&st=135&p=92057&#entry92057
+    _40Line,
+    _20Line,
+    _100Line,
+    _1000Line,
+    /// A mode other than the line sprints (cheese, survival, practice, custom
+    /// games, etc.); holds the raw mode id so that it survives a round trip.
+    ///
+    /// The ids of the non-sprint modes haven't been checked against the
+    /// jstris client so they don't get variants of their own yet.
+    Other(u16),
+}
+
+impl GameMode {
+    pub const fn from_id(id: u16) -> Self {
+        use GameMode::*;
+
+        match id {
+            1 => _40Line,
+            2 => _20Line,
+            3 => _100Line,
+            4 => _1000Line,
+            other => Other(other),
+        }
+    }
+
+    /// The mode's id, as it appears in replays.
+    pub const fn id(self) -> u16 {
+        use GameMode::*;
+
+        match self {
+            _40Line => 1,
+            _20Line => 2,
+            _100Line => 3,
+            _1000Line => 4,
+            Other(id) => id,
+        }
+    }
+
+    /// The number of lines to clear, for the line sprints.
+    pub const fn sprint_lines(self) -> Option<u32> {
+        use GameMode::*;
+
+        match self {
+            _20Line => Some(20),
+            _40Line => Some(40),
+            _100Line => Some(100),
+            _1000Line => Some(1000),
+            Other(_) => None,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for GameMode {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        u16::deserialize(deserializer).map(GameMode::from_id)
+    }
+}
+
+impl Serialize for GameMode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_u16(self.id())
+    }
+}
+
+#[cfg(test)]
+mod test_game_mode {
+    use super::*;
+
+    #[test]
+    fn ids() {
+        for id in 0..=300 {
+            assert_eq!(GameMode::from_id(id).id(), id);
+        }
+        assert_eq!(GameMode::from_id(1), GameMode::_40Line);
+        assert_eq!(GameMode::from_id(7), GameMode::Other(7));
+    }
+
+    #[test]
+    fn practice_replay() {
+        // A custom game: custom seed and a mode id we don't know.
+        let json = test_util::SAMPLE_JSON
+            .replace(r#""seed":"c07yl8j""#, r#""seed":"myseed42""#)
+            .replace(r#""m":1"#, r#""m":7"#);
+        let replay = decode_json(json).unwrap();
+
+        assert_eq!(replay.metadata.game_mode, GameMode::Other(7));
+        assert_eq!(AsRef::<[u8]>::as_ref(&replay.metadata.seed), b"myseed42");
+
+        let reencoded = decode_uri_string(encode_uri_string(&replay).unwrap()).unwrap();
+        assert_eq!(reencoded, replay);
+    }
+
+    #[test]
+    fn mixed_modes() {
+        for (m, mode) in [
+            (2, GameMode::_20Line),
+            (4, GameMode::_1000Line),
+            (0, GameMode::Other(0)),
+            (31, GameMode::Other(31)),
+            (40_000, GameMode::Other(40_000)),
+        ] {
+            let json = test_util::SAMPLE_JSON.replace(r#""m":1"#, &format!(r#""m":{m}"#));
+            let replay = decode_json(&json).unwrap();
+            assert_eq!(replay.metadata.game_mode, mode);
+
+            let reencoded = serde_json::to_string(&replay).unwrap();
+            assert!(reencoded.contains(&format!(r#""m":{m},"#)), "{reencoded}");
+        }
+
+        assert_eq!(GameMode::_40Line.sprint_lines(), Some(40));
+        assert_eq!(GameMode::Other(31).sprint_lines(), None);
+    }
+}
 
-#[derive(
-    Debug,
-    Copy,
-    Clone,
-    PartialEq,
-    Eq,
-    PartialOrd,
-    Ord,
-    Hash,
-    Serialize_repr,
-    Deserialize_repr,
-    Default,
-)]
-#[repr(u8)]
-pub enum SoundEffects {
-    Nullpomino = 0,
-    Yotipo = 1,
-    Rainforest = 2,
-    TetraX = 3,
-    #[default]
-    None = 4,
+/// The metadata's `r` field.
+///
+/// What this is isn't known. It's `0` in the sprint replays we've looked at
+/// and doesn't affect how the replay plays back; a ranked flag, a restart
+/// count, or a randomizer id (sprints always use a 7-bag) are all plausible.
+/// Until we know more this just carries the raw value so that it survives a
+/// round trip.
+///
+/// Whatever it is (flags or a counter), all 16 bits are kept as is; there
+/// are no accessors for individual bits since their meanings (if any) are
+/// also unknown.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct UnknownR(pub u16);
+
+impl UnknownR {
+    /// The value as it appears in the replay.
+    pub const fn raw(self) -> u16 {
+        self.0
+    }
 }
 
-#[derive(
-    Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize_repr, Deserialize_repr,
-)]
-#[repr(u8)]
-pub enum SoftDropSpeed {
-    Slow = 0,
-    Medium = 1,
-    Fast = 2,
-    Ultra = 3,
-    Instant = 4,
+impl From<u16> for UnknownR {
+    fn from(raw: u16) -> Self {
+        Self(raw)
+    }
 }
 
-impl SoftDropSpeed {
-    // https://harddrop.com/forums/index.php?showtopic=7087&st=135&p=92057&#entry92057
-    pub const fn steps(self) -> u8 {
-        use SoftDropSpeed::*;
+#[cfg(test)]
+mod test_unknown_r {
+    use super::*;
 
-        match self {
-            Slow | Medium => 0,
-            Fast => 1,
-            Ultra => 2,
-            Instant => 20,
+    fn round_trip(json: &str) -> String {
+        let replay = decode_json(json).unwrap();
+        serde_json::to_string(&Metadata::from(&replay)).unwrap()
+    }
+
+    #[test]
+    fn present() {
+        let json = test_util::SAMPLE_JSON.replace(r#""r":0"#, r#""r":7"#);
+        assert_eq!(decode_json(&json).unwrap().metadata.r, Some(UnknownR(7)));
+        assert!(round_trip(&json).contains(r#""r":7"#));
+
+        assert!(round_trip(test_util::SAMPLE_JSON).contains(r#""r":0"#));
+    }
+
+    #[test]
+    fn every_bit_survives() {
+        for raw in [1, 0x80, 0x100, 0xFFFF] {
+            let json = test_util::SAMPLE_JSON.replace(r#""r":0"#, &format!(r#""r":{raw}"#));
+            let r = decode_json(&json).unwrap().metadata.r.unwrap();
+            assert_eq!(r.raw(), raw);
+            let metadata: serde_json::Value = serde_json::from_str(&round_trip(&json)).unwrap();
+            assert_eq!(metadata["r"], raw);
         }
     }
+
+    #[test]
+    fn absent() {
+        let json = test_util::SAMPLE_JSON.replace(r#","r":0"#, "");
+        assert_eq!(decode_json(&json).unwrap().metadata.r, None);
+        assert!(!round_trip(&json).contains(r#""r""#));
+    }
 }
 
+/// The metadata's `bbs` (big blocks) field; `0` or `1` on the wire.
+///
+/// The byte is kept as is so replays with other values still decode (and
+/// re-encode unchanged); anything non-zero is taken to mean big blocks.
 #[derive(
-    Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize_repr, Deserialize_repr,
+    Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
 )]
-#[repr(u8)]
-pub enum GameMode {
-    // TODO: non-sprint modes?
-    // See: https://harddrop.com/forums/index.php?showtopic=7087&st=135&p=92057&#entry92057
-    _40Line = 1,
-    _20Line = 2,
-    _100Line = 3,
-    _1000Line = 4,
+#[serde(transparent)]
+pub struct BigBlocks(pub u8);
+
+impl BigBlocks {
+    /// Whether blocks are big.
+    pub const fn is_big(self) -> bool {
+        self.0 != 0
+    }
+
+    /// The value as it appears in the replay.
+    pub const fn raw(self) -> u8 {
+        self.0
+    }
+}
+
+impl From<bool> for BigBlocks {
+    fn from(big: bool) -> Self {
+        Self(big as u8)
+    }
+}
+
+#[cfg(test)]
+mod test_big_blocks {
+    use super::*;
+
+    fn with_bbs(bbs: &str) -> String {
+        test_util::SAMPLE_JSON.replace(r#""r":0"#, &format!(r#""r":0,"bbs":{bbs}"#))
+    }
+
+    #[test]
+    fn wire_format() {
+        for (bbs, big) in [("0", false), ("1", true)] {
+            let replay = decode_json(with_bbs(bbs)).unwrap();
+            assert_eq!(replay.metadata.bbs, Some(BigBlocks::from(big)));
+            assert_eq!(replay.metadata.big_blocks(), big);
+
+            let json = serde_json::to_string(&replay.metadata).unwrap();
+            assert!(json.contains(&format!(r#""bbs":{bbs}"#)), "{json}");
+        }
+
+        assert!(decode_json(with_bbs("true")).is_err());
+    }
+
+    #[test]
+    fn other_values() {
+        let replay = decode_json(with_bbs("2")).unwrap();
+        assert_eq!(replay.metadata.bbs, Some(BigBlocks(2)));
+        assert!(replay.metadata.big_blocks());
+
+        let json = serde_json::to_string(&replay.metadata).unwrap();
+        assert!(json.contains(r#""bbs":2"#), "{json}");
+    }
+
+    #[test]
+    fn absent() {
+        let replay = test_util::sample();
+        assert_eq!(replay.metadata.bbs, None);
+        assert!(!replay.metadata.big_blocks());
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -554,8 +3242,55 @@ pub struct GameSeed {
 }
 
 impl GameSeed {
+    /// The longest seed that's accepted (and the size of the backing array).
     // replay:607532
     pub const MAX_LEN: usize = 10;
+
+    /// The seed as given by jstris; this is exactly what the PRNG is seeded
+    /// with (see [`JstrisBag::new`](rng::JstrisBag::new)).
+    pub fn as_str(&self) -> &str {
+        self.as_ref()
+    }
+
+    /// The length of the seed in bytes; seeds are never empty.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// A 6 character seed (like the ones jstris generates), seeded from the
+    /// process's hash map randomness and the current time.
+    ///
+    /// See [`GameSeed::random_from`] to use some other source of randomness.
+    pub fn random() -> GameSeed {
+        use std::{
+            collections::hash_map::RandomState,
+            hash::{BuildHasher, Hasher},
+            time::SystemTime,
+        };
+
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos()),
+        );
+        Self::random_from(hasher.finish())
+    }
+
+    /// The 6 character seed picked by `bits` (e.g. `rng.next_u64()`); every
+    /// character is a lowercase letter or a digit.
+    pub fn random_from(mut bits: u64) -> GameSeed {
+        const CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+
+        let mut bytes = [0; Self::MAX_LEN];
+        for b in &mut bytes[..6] {
+            *b = CHARSET[(bits % CHARSET.len() as u64) as usize];
+            bits /= CHARSET.len() as u64;
+        }
+
+        GameSeed { bytes, len: 6 }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -578,7 +3313,7 @@ impl TryFrom<&str> for GameSeed {
 
         for (c, &b) in out.iter_mut().zip(str.as_bytes().iter()) {
             match b {
-                b'a'..=b'z' | b'0'..=b'9' => *c = b,
+                b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' => *c = b,
                 _ => return Err(GameSeedParseError::InvalidChar { c: b }),
             }
         }
@@ -598,7 +3333,9 @@ impl AsRef<[u8]> for GameSeed {
 
 impl AsRef<str> for GameSeed {
     fn as_ref(&self) -> &str {
-        unsafe { std::str::from_utf8_unchecked(self.bytes.as_ref()) }
+        // Only the first `len` bytes are part of the seed; the PRNG is seeded
+        // with this so the padding must not be included.
+        unsafe { std::str::from_utf8_unchecked(AsRef::<[u8]>::as_ref(self)) }
     }
 }
 
@@ -610,11 +3347,8 @@ impl Debug for GameSeed {
 
 impl Display for GameSeed {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(fmt, "\"")?;
-        for &c in AsRef::<[u8]>::as_ref(&self.bytes) {
-            write!(fmt, "{}", c as char)?
-        }
-        write!(fmt, "\"")
+        // Only the first `len` bytes; the rest of the array is padding.
+        write!(fmt, "\"{}\"", self.as_str())
     }
 }
 
@@ -635,20 +3369,456 @@ impl Serialize for GameSeed {
     where
         S: serde::Serializer,
     {
-        serializer.serialize_str(unsafe { std::str::from_utf8_unchecked(self.as_ref()) })
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod test_game_seed {
+    use super::*;
+
+    #[test]
+    fn max_len() {
+        let longest = "a".repeat(GameSeed::MAX_LEN);
+        let seed = GameSeed::try_from(longest.as_str()).unwrap();
+        assert_eq!(seed.as_str(), longest);
+
+        let too_long = "a".repeat(GameSeed::MAX_LEN + 1);
+        assert!(matches!(
+            GameSeed::try_from(too_long.as_str()),
+            Err(GameSeedParseError::WrongLength { len }) if len == GameSeed::MAX_LEN + 1
+        ));
+    }
+
+    #[test]
+    fn mixed_case() {
+        let seed = GameSeed::try_from("Ab3XyZ").unwrap();
+        assert_eq!(seed.as_str(), "Ab3XyZ");
+
+        // The PRNG hashes the seed's characters as is, so case matters:
+        let lower = GameSeed::try_from("ab3xyz").unwrap();
+        assert_ne!(
+            rng::JstrisBag::new(seed).peek(14),
+            rng::JstrisBag::new(lower).peek(14)
+        );
+
+        for bad in ["ab-c", "ab c", "ab\u{e9}"] {
+            assert!(
+                matches!(
+                    GameSeed::try_from(bad),
+                    Err(GameSeedParseError::InvalidChar { .. })
+                ),
+                "{bad}"
+            );
+        }
+    }
+
+    #[test]
+    fn short() {
+        let seed = GameSeed::try_from("abc").unwrap();
+
+        assert_eq!(seed.len(), 3);
+        assert_eq!(seed.as_str(), "abc");
+        assert_eq!(seed.to_string(), r#""abc""#);
+        assert_eq!(format!("{seed:?}"), r#""abc""#);
+
+        let json = serde_json::to_string(&seed).unwrap();
+        assert_eq!(json, r#""abc""#);
+        assert_eq!(serde_json::from_str::<GameSeed>(&json).unwrap(), seed);
+    }
+
+    #[test]
+    fn random() {
+        for bits in [0, 1, 12345, u64::MAX] {
+            let seed = GameSeed::random_from(bits);
+            assert_eq!(seed.len(), 6);
+            // Anything generated has to parse:
+            assert_eq!(GameSeed::try_from(seed.as_str()).unwrap(), seed);
+        }
+        assert_eq!(GameSeed::random_from(0).as_str(), "aaaaaa");
+        assert_ne!(GameSeed::random_from(1), GameSeed::random_from(2));
+
+        assert_eq!(GameSeed::random().len(), 6);
+        assert_eq!(GameSeed::try_from("c07yl8j").unwrap().len(), 7);
     }
 }
 
+/// Decodes a replay in the URI encoded LZ string form.
+///
+/// Surrounding whitespace is ignored and full URLs are accepted, in which case
+/// the replay is taken from the `r` query parameter.
 pub fn decode_uri_string(replay_uri_string: impl AsRef<[u8]>) -> Result<JstrisReplay, DecodeError> {
-    let bytes = replay_uri_string.as_ref();
+    decode_uri_string_with(replay_uri_string, DecodeOptions::default())
+}
+
+/// [`decode_uri_string`] with non-default [`DecodeOptions`].
+pub fn decode_uri_string_with(
+    replay_uri_string: impl AsRef<[u8]>,
+    options: DecodeOptions,
+) -> Result<JstrisReplay, DecodeError> {
+    let bytes = extract_uri_string(replay_uri_string.as_ref())?;
     let compressed = bytes.iter().copied().map(u32::from).collect::<Vec<_>>();
 
     let str = lz_str::decompress_uri(&compressed).ok_or(DecodeError::LzStrDecodeError)?;
-    decode_json(str)
+    decode_json_with(str, options)
+}
+
+/// Knobs for [`decode_json_with`] and [`decode_uri_string_with`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct DecodeOptions {
+    /// Drop the final event if it looks like padding; see
+    /// [`JstrisReplay::strip_padding`].
+    pub strip_padding: bool,
+}
+
+// Same as `JstrisReplay` but with the event data left as bytes so that errors
+// from decoding the events aren't folded into `serde_json::Error`.
+#[serde_as]
+#[derive(Deserialize)]
+struct RawJstrisReplay {
+    #[serde(rename = "c")]
+    metadata: Metadata,
+    #[serde(rename = "d")]
+    #[serde_as(as = "Base64")]
+    data: Vec<u8>,
+}
+
+fn extract_uri_string(input: &[u8]) -> Result<&[u8], DecodeError> {
+    let input = input.trim_ascii();
+
+    let is_url = input.starts_with(b"http://") || input.starts_with(b"https://");
+    if !is_url {
+        return Ok(input);
+    }
+
+    let query = match input.iter().position(|&b| b == b'?') {
+        Some(idx) => &input[(idx + 1)..],
+        None => return Err(DecodeError::MissingReplayParameter),
+    };
+    let query = query.split(|&b| b == b'#').next().unwrap_or(query);
+
+    query
+        .split(|&b| b == b'&')
+        .find_map(|param| param.strip_prefix(b"r="))
+        .ok_or(DecodeError::MissingReplayParameter)
+}
+
+#[cfg(test)]
+mod test_decode_uri_string {
+    use super::*;
+
+    fn encoded() -> String {
+        encode_uri_string(&test_util::sample()).unwrap()
+    }
+
+    #[test]
+    fn whitespace() {
+        let sample = test_util::sample();
+
+        assert_eq!(
+            decode_uri_string(format!("{}\n", encoded())).unwrap(),
+            sample
+        );
+        assert_eq!(
+            decode_uri_string(format!("  {}\t ", encoded())).unwrap(),
+            sample
+        );
+    }
+
+    #[test]
+    fn url() {
+        let sample = test_util::sample();
+
+        let url = format!("https://jstris.jezevec10.com/replay/?r={}", encoded());
+        assert_eq!(decode_uri_string(&url).unwrap(), sample);
+
+        let url = format!(
+            "https://jstris.jezevec10.com/replay/?x=1&r={}&y=2#top\n",
+            encoded()
+        );
+        assert_eq!(decode_uri_string(&url).unwrap(), sample);
+
+        assert!(matches!(
+            decode_uri_string("https://jstris.jezevec10.com/replay/?x=1"),
+            Err(DecodeError::MissingReplayParameter)
+        ));
+    }
+}
+
+impl TryFrom<RawJstrisReplay> for JstrisReplay {
+    type Error = DecodeError;
+
+    fn try_from(RawJstrisReplay { metadata, data }: RawJstrisReplay) -> Result<Self, Self::Error> {
+        Ok(JstrisReplay {
+            metadata,
+            data: data.try_into()?,
+        })
+    }
 }
 
 pub fn decode_json(json: impl AsRef<str>) -> Result<JstrisReplay, DecodeError> {
-    serde_json::from_str::<JstrisReplay>(json.as_ref()).map_err(DecodeError::JsonDecodeError)
+    decode_json_with(json, DecodeOptions::default())
+}
+
+/// [`decode_json`] with non-default [`DecodeOptions`].
+pub fn decode_json_with(
+    json: impl AsRef<str>,
+    options: DecodeOptions,
+) -> Result<JstrisReplay, DecodeError> {
+    let mut replay: JstrisReplay = serde_json::from_str::<RawJstrisReplay>(json.as_ref())
+        .map_err(DecodeError::JsonDecodeError)?
+        .try_into()?;
+
+    if options.strip_padding {
+        replay.strip_padding();
+    }
+
+    Ok(replay)
+}
+
+/// Like [`decode_json`] but stops after the replay instead of erroring on
+/// anything following it; returns the replay and the rest of `json`, starting
+/// right after the replay's closing brace.
+///
+/// Useful for dumps with several replays concatenated together, or to report
+/// trailing garbage instead of just failing to decode.
+pub fn decode_json_partial(json: &str) -> Result<(JstrisReplay, &str), DecodeError> {
+    let mut stream = serde_json::Deserializer::from_str(json).into_iter::<RawJstrisReplay>();
+
+    let raw = stream
+        .next()
+        .ok_or_else(|| {
+            DecodeError::JsonDecodeError(serde_json::Error::custom("expected a replay"))
+        })?
+        .map_err(DecodeError::JsonDecodeError)?;
+
+    Ok((raw.try_into()?, &json[stream.byte_offset()..]))
+}
+
+/// Like [`decode_json`] but for a replay that's already been parsed, e.g. one
+/// pulled out of a larger JSON document.
+///
+/// To decode a replay that is a field of some other type, `JstrisReplay`'s
+/// `Deserialize` impl can be used directly instead; errors in the event data
+/// are then reported as the deserializer's error type.
+pub fn decode_from_value(value: serde_json::Value) -> Result<JstrisReplay, DecodeError> {
+    serde_json::from_value::<RawJstrisReplay>(value)
+        .map_err(DecodeError::JsonDecodeError)?
+        .try_into()
+}
+
+/// A replay decoded by [`decode_json_any_version`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AnyVersionReplay {
+    /// The version the replay says it is, whether or not we support it.
+    pub version: (u8, u8),
+    /// If `version` isn't one we support, this replay's
+    /// [`metadata.version`](Metadata::version) is the newest version we
+    /// know of instead.
+    pub replay: JstrisReplay,
+}
+
+/// Like [`decode_json`] but doesn't reject replays from versions of jstris we
+/// don't know about; the replay is decoded as if it were the newest version
+/// we support.
+pub fn decode_json_any_version(json: impl AsRef<str>) -> Result<AnyVersionReplay, DecodeError> {
+    let mut value: serde_json::Value =
+        serde_json::from_str(json.as_ref()).map_err(DecodeError::JsonDecodeError)?;
+
+    // Not indexing with `value["c"]["v"]`; that panics if `value` or `c`
+    // isn't an object.
+    let slot = value
+        .pointer_mut("/c/v")
+        .ok_or_else(|| DecodeError::JsonDecodeError(serde_json::Error::missing_field("c.v")))?;
+    let ver = serde_json::from_value(slot.take()).map_err(DecodeError::JsonDecodeError)?;
+    let version = parse_version(ver)
+        .map_err(|err| DecodeError::JsonDecodeError(serde_json::Error::custom(err)))?;
+
+    *slot = match serde_json::to_value(ver)
+        .and_then(serde_json::from_value::<ExpectedJstrisReplayVersion<3, 0>>)
+    {
+        Ok(supported) => serde_json::to_value(supported),
+        Err(_) => serde_json::to_value(ExpectedJstrisReplayVersion::<3, 0>::new()),
+    }
+    .map_err(DecodeError::JsonDecodeError)?;
+
+    Ok(AnyVersionReplay {
+        version,
+        replay: decode_from_value(value)?,
+    })
+}
+
+#[derive(Deserialize)]
+struct MetadataOnly {
+    #[serde(rename = "c")]
+    metadata: Metadata,
+    // `d` is skipped over without being decoded.
+}
+
+/// Decodes only the metadata of a replay in JSON form, skipping the event
+/// data.
+pub fn decode_metadata_only(json: impl AsRef<str>) -> Result<Metadata, DecodeError> {
+    serde_json::from_str::<MetadataOnly>(json.as_ref())
+        .map(|m| m.metadata)
+        .map_err(DecodeError::JsonDecodeError)
+}
+
+impl From<&JstrisReplay> for Metadata {
+    fn from(replay: &JstrisReplay) -> Self {
+        replay.metadata.clone()
+    }
+}
+
+#[cfg(test)]
+mod test_decode {
+    use super::*;
+
+    fn with_data(data: &str) -> String {
+        let metadata = serde_json::to_string(&test_util::sample().metadata).unwrap();
+        format!(r#"{{"c":{metadata},"d":"{data}"}}"#)
+    }
+
+    #[test]
+    fn bad_base64() {
+        let err = decode_json(with_data("not base64!")).unwrap_err();
+        assert!(matches!(err, DecodeError::JsonDecodeError(_)), "{err:?}");
+    }
+
+    #[test]
+    fn json_error_source() {
+        use std::error::Error as _;
+
+        let err = decode_json("{").unwrap_err();
+        let source = err.source().expect("the JSON error should be the source");
+        let json_err = source.downcast_ref::<serde_json::Error>().unwrap();
+        assert!(json_err.is_eof());
+    }
+
+    #[test]
+    fn misaligned() {
+        // 6 bytes: valid base64 but 3 events.
+        let err = decode_json(with_data("AeAD5wcy")).unwrap_err();
+        assert!(
+            matches!(err, DecodeError::MisalignedEventData { num_bytes: 6 }),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn partial() {
+        let json = test_util::SAMPLE_JSON.trim();
+        let sample = test_util::sample();
+
+        assert_eq!(decode_json_partial(json).unwrap(), (sample.clone(), ""));
+
+        let concatenated = format!("{json}\n{json}");
+        let (first, rest) = decode_json_partial(&concatenated).unwrap();
+        assert_eq!(first, sample);
+        assert_eq!(decode_json_partial(rest).unwrap(), (sample.clone(), ""));
+
+        let trailing = format!("{json}garbage");
+        let (_, rest) = decode_json_partial(&trailing).unwrap();
+        assert_eq!(rest, "garbage");
+        assert!(decode_json(&trailing).is_err());
+
+        for bad in ["", "  ", "{", "garbage"] {
+            let err = decode_json_partial(bad).unwrap_err();
+            assert!(matches!(err, DecodeError::JsonDecodeError(_)), "{err:?}");
+        }
+    }
+
+    #[test]
+    fn metadata_only() {
+        let sample = test_util::sample();
+        assert_eq!(
+            decode_metadata_only(test_util::SAMPLE_JSON).unwrap(),
+            Metadata::from(&sample)
+        );
+
+        // The event data isn't looked at:
+        let metadata = decode_metadata_only(with_data("not base64!")).unwrap();
+        assert_eq!(metadata, sample.metadata);
+    }
+
+    #[test]
+    fn absent_handling() {
+        let sample = test_util::sample();
+        assert_eq!(sample.metadata.das, Some(83));
+        assert_eq!(sample.metadata.arr, None);
+
+        let explicit = test_util::SAMPLE_JSON.replace(r#""das":83"#, r#""das":83,"arr":0"#);
+        let explicit = decode_json(explicit).unwrap();
+        assert_eq!(explicit.metadata.arr, Some(0));
+
+        // And they stay that way:
+        let json = serde_json::to_string(&sample.metadata).unwrap();
+        assert!(!json.contains("arr"), "{json}");
+        let json = serde_json::to_string(&explicit.metadata).unwrap();
+        assert!(json.contains(r#""arr":0"#), "{json}");
+    }
+
+    #[test]
+    fn aligned() {
+        let replay = decode_json(with_data("AeAD5wcyDac=")).unwrap();
+        assert_eq!(replay.data.len(), 4);
+    }
+
+    #[test]
+    fn any_version() {
+        let sample = test_util::sample();
+        let future = test_util::SAMPLE_JSON.replace(r#""v":3.3"#, r#""v":4.0"#);
+
+        assert!(matches!(
+            decode_json(&future),
+            Err(DecodeError::JsonDecodeError(_))
+        ));
+
+        let decoded = decode_json_any_version(&future).unwrap();
+        assert_eq!(decoded.version, (4, 0));
+        assert_eq!(decoded.replay.metadata.version.version(), (3, 3));
+        assert_eq!(decoded.replay, sample);
+
+        // Supported versions are kept as they are:
+        let old = test_util::SAMPLE_JSON.replace(r#""v":3.3"#, r#""v":3.1"#);
+        let decoded = decode_json_any_version(old).unwrap();
+        assert_eq!(decoded.version, (3, 1));
+        assert_eq!(decoded.replay.metadata.version.version(), (3, 1));
+    }
+
+    #[test]
+    fn any_version_not_an_object() {
+        for bad in ["[]", "5", r#"{"c":5}"#, r#"{"c":{}}"#] {
+            let err = decode_json_any_version(bad).unwrap_err();
+            assert!(
+                matches!(err, DecodeError::JsonDecodeError(_)),
+                "{bad}: {err:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn embedded() {
+        #[derive(Deserialize)]
+        struct Envelope {
+            id: u32,
+            replay: JstrisReplay,
+        }
+
+        let json = format!(r#"{{"id":607532,"replay":{}}}"#, test_util::SAMPLE_JSON);
+        let envelope: Envelope = serde_json::from_str(&json).unwrap();
+        assert_eq!(envelope.id, 607532);
+        assert_eq!(envelope.replay, test_util::sample());
+
+        let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let replay = decode_from_value(value["replay"].take()).unwrap();
+        assert_eq!(replay, test_util::sample());
+
+        let value = serde_json::from_str(&with_data("AeAD5wcy")).unwrap();
+        assert!(matches!(
+            decode_from_value(value),
+            Err(DecodeError::MisalignedEventData { num_bytes: 6 })
+        ));
+    }
 }
 
 pub fn encode_uri_string(replay: &JstrisReplay) -> Result<String, serde_json::Error> {
@@ -657,4 +3827,206 @@ pub fn encode_uri_string(replay: &JstrisReplay) -> Result<String, serde_json::Er
     Ok(vec.iter().map(|c| char::try_from(*c).unwrap()).collect())
 }
 
-// TODO: roundtrip tests
+#[derive(Debug, Error)]
+pub enum EncodeError {
+    #[error("error encountered when encoding the replay as JSON: {}", .0)]
+    JsonEncodeError(serde_json::Error),
+    #[error("error encountered when writing the encoded replay: {}", .0)]
+    IoError(#[from] io::Error),
+    #[error("the compressed replay contained a non-ASCII code point: {code:#X}")]
+    NonAsciiOutput { code: u32 },
+}
+
+impl JstrisReplay {
+    /// Like [`encode_uri_string`] but writes the encoded replay to `w` instead
+    /// of collecting it into a `String`.
+    pub fn encode_uri_writer<W: Write>(&self, mut w: W) -> Result<(), EncodeError> {
+        let json = serde_json::to_string(self).map_err(EncodeError::JsonEncodeError)?;
+        let compressed = lz_str::compress_uri(&json);
+
+        // The URI alphabet is all ASCII, so each code point is one byte. These
+        // go out a chunk at a time so there's no third full-size buffer.
+        let mut buf = [0; 4096];
+        for chunk in compressed.chunks(buf.len()) {
+            for (byte, &code) in buf.iter_mut().zip(chunk) {
+                *byte = u8::try_from(code)
+                    .ok()
+                    .filter(u8::is_ascii)
+                    .ok_or(EncodeError::NonAsciiOutput { code })?;
+            }
+            w.write_all(&buf[..chunk.len()])?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_encode_uri_writer {
+    use super::*;
+
+    #[test]
+    fn to_vec() {
+        let replay = test_util::sample();
+
+        let mut out = Vec::new();
+        replay.encode_uri_writer(&mut out).unwrap();
+
+        assert_eq!(out, encode_uri_string(&replay).unwrap().as_bytes());
+        assert_eq!(decode_uri_string(&out).unwrap(), replay);
+    }
+
+    #[test]
+    fn several_chunks() {
+        let events: Vec<_> = (0..4000)
+            .map(|i| (Input::ALL[i % 11], (i * 37 + i * i % 13) as u32))
+            .collect();
+        let replay = test_util::with_events(&events);
+
+        let mut out = Vec::new();
+        replay.encode_uri_writer(&mut out).unwrap();
+
+        assert!(out.len() > 2 * 4096, "{}", out.len());
+        assert_eq!(out, encode_uri_string(&replay).unwrap().as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod test_thread_safety {
+    use super::*;
+
+    const fn send_sync<T: Send + Sync>() {}
+
+    // Replays are decoded and analysed in parallel when scraping; this only
+    // needs to compile.
+    #[test]
+    fn core_types_are_send_and_sync() {
+        send_sync::<JstrisReplay>();
+        send_sync::<EventList>();
+        send_sync::<EventListIter<'_>>();
+        send_sync::<AbsoluteEventListIter<'_>>();
+        send_sync::<Metadata>();
+        send_sync::<GameSeed>();
+        send_sync::<AnyVersionReplay>();
+        send_sync::<DecodeError>();
+        send_sync::<EncodeError>();
+        send_sync::<FromEventsError>();
+        send_sync::<archive::ArchiveError>();
+
+        send_sync::<rng::AleaPrng>();
+        send_sync::<rng::JstrisBag>();
+        send_sync::<placements::Placements<'_>>();
+        send_sync::<sim::Simulation<'_>>();
+    }
+}
+
+#[cfg(test)]
+mod test_round_trip {
+    use super::*;
+
+    /// Some of the replays from `tests/corpus/` (which `tests/corpus.rs` round
+    /// trips all of). Only `sample-40l` came from jstris. The `synthetic-*`
+    /// ones were made with this crate's own encoder, so they cover odd
+    /// lengths and long games but can't catch a mismatch between our encoding
+    /// and jstris's.
+    // TODO: add more real replays
+    const FIXTURES: [(&str, &str); 3] = [
+        ("sample-40l", include_str!("../tests/corpus/sample-40l.txt")),
+        (
+            "synthetic-short-odd",
+            include_str!("../tests/corpus/synthetic-short-odd.txt"),
+        ),
+        (
+            "synthetic-1000l-slow",
+            include_str!("../tests/corpus/synthetic-1000l-slow.txt"),
+        ),
+    ];
+
+    #[test]
+    fn uri_string() {
+        for (name, uri) in FIXTURES {
+            let replay = decode_uri_string(uri.trim()).unwrap();
+
+            let encoded = encode_uri_string(&replay).unwrap();
+            let decoded = decode_uri_string(&encoded).unwrap();
+            assert_eq!(decoded, replay, "{name}");
+
+            // Stable from here on:
+            assert_eq!(encode_uri_string(&decoded).unwrap(), encoded, "{name}");
+        }
+    }
+
+    #[test]
+    fn json() {
+        // Escaping and number formatting aside, the JSON matches what jstris
+        // produced.
+        let original: serde_json::Value = serde_json::from_str(test_util::SAMPLE_JSON).unwrap();
+        let json = serde_json::to_string(&test_util::sample()).unwrap();
+        let reencoded: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(reencoded, original);
+    }
+
+    #[test]
+    fn stripped_padding() {
+        let options = DecodeOptions {
+            strip_padding: true,
+        };
+
+        for (name, uri) in FIXTURES {
+            let replay = decode_uri_string_with(uri.trim(), options).unwrap();
+
+            let encoded = encode_uri_string(&replay).unwrap();
+            let decoded = decode_uri_string_with(&encoded, options).unwrap();
+            assert_eq!(decoded, replay, "{name}");
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test_util {
+    use super::*;
+
+    /// The 40L sprint embedded in `main.rs`.
+    pub const SAMPLE_JSON: &str = r#"{"c":{"v":3.3,"softDropId":4,"gameStart":1684543650931,"gameEnd":1684543666545,"seed":"c07yl8j","m":1,"bs":0,"se":0,"das":83,"r":0},"d":"AeAD5wcyDacP0BQ3FRIWWhZSGVUZUhwXHZEi4yRXJFMmeiZzKRAsRyy6LdEuJjMTOFc61T4nQBFFU0nHS+RQZ1CxVgNYMFlXWvpcRlzRYhNkF2WgaHVq8mz3bZputHKAdId3wnv3e\/J+NoK3hZGK0433jfOU15aanRGdhaIXqHeqKq31rvCyJ7UgulK+J74iv7q\/ssenyeXNp88m0IHVs9fX2Yrc8d1l4PfjdORw6bfr1fAn8jH3c\/in+KP6ivqD\/iAAlwDWAyEIYwp3CnMLwBECEtcU5BfhGucc9CEwI6coRSqXLoAz0jXhO8c9mkAkQyBIYk73XEZc8GJCZLFpx221bjFxV3OReNN8B3wDgaeCuoVWigeMEJFik1GVJ5cwmgWccp5Hn7ahQaaDqoeuMLJHtCW397sRwFPDp8W0x8HKp8zx0jPWt9qF2oHeF98g44XkYufH58LqdO3R8Ify2vPk+AD8RwBVAecEOgZAC5IPdxHxEpUWlx53IOAmMiyHLso25zlkPVBAJ0NBQ1ZIg0vnTfpPQFSCWRdaQVrlX4dgoWXjaCdoI2paalNvYHFHc1p0snZ1dpJ593qmfdGDE4U3hqSJgIzHjvGUQ5cnlyOcl57Kn\/Cj9aVSqfep8q4RrrWyF7QatkW2QbvHvaq\/kMTSyMXLl9Gn1iTcB+E15EflsOnF6wLup+\/28ZH2w\/dX91P5evlzAGcAYwJKAkMIRwhDCUoJQwpWEbcTkBjSHTce8R9VI6ck0CoyLWcvATRTNec14ziqOKM\/xz\/DQRpBE0HmSadMFFPXX8Ff5WNXapdx4HYXeGZ60IEXhJCJ4o+3lPGVFZjXndCgB6Pgp3WpIq0nsqGzBbXXu5e\/BsSHx9rH4MkkzRLO0c\/n\/\/A="}"#;
+
+    pub fn sample() -> JstrisReplay {
+        decode_json(SAMPLE_JSON).unwrap()
+    }
+
+    /// An event list made of `words` as is (e.g. to put payloads after
+    /// events; see [`word`]).
+    pub fn from_words(words: &[u16]) -> EventList {
+        EventList {
+            inner: words.iter().map(|&w| Event::try_from(w).unwrap()).collect(),
+            offsets: OffsetCache::default(),
+        }
+    }
+
+    /// The word for `input` at the 12-bit timestamp `millis`.
+    pub fn word(millis: u16, input: Input) -> u16 {
+        millis << 4 | input.code() as u16
+    }
+
+    /// The sample's metadata with `events` (times are in milliseconds from
+    /// the start of the game) as the event data.
+    ///
+    /// Doesn't account for gaps of more than 4096ms between events.
+    pub fn with_events(events: &[(Input, u32)]) -> JstrisReplay {
+        let inner = events
+            .iter()
+            .map(|&(input, ms)| Event {
+                timestamp: TwelveBitMillisecondTimestamp((ms % 0x1000) as u16),
+                input,
+            })
+            .collect();
+
+        JstrisReplay {
+            data: EventList {
+                inner,
+                offsets: OffsetCache::default(),
+            },
+            ..sample()
+        }
+    }
+}
@@ -0,0 +1,175 @@
+//! Rendering a replay's inputs as a MIDI file (behind the `midi` feature), so
+//! the rhythm of a run can be listened to.
+//!
+//! The file is a single track (format 0) with one tick per millisecond. Each
+//! input plays its [`midi_note`] for [`NOTE_LENGTH_MILLIS`] at the time it
+//! happened.
+
+use crate::{Input, JstrisReplay};
+
+/// How long each input's note is held for.
+pub const NOTE_LENGTH_MILLIS: u32 = 100;
+
+/// Ticks per quarter note; with the tempo set to a quarter note per second
+/// this makes a tick a millisecond.
+const TICKS_PER_QUARTER: u16 = 1000;
+const MICROS_PER_QUARTER: u32 = 1_000_000;
+
+const CHANNEL: u8 = 0;
+const VELOCITY: u8 = 100;
+
+/// The note (as a MIDI key number; 60 is middle C) played for `input`.
+///
+/// The player's inputs are roughly on a C major pentatonic scale with the
+/// drops in the bass; system events are kept away from both.
+pub const fn midi_note(input: Input) -> u8 {
+    use Input::*;
+
+    match input {
+        MoveLeft => 60,
+        MoveRight => 62,
+        DasLeft => 55,
+        DasRight => 57,
+        RotateLeft => 64,
+        RotateRight => 67,
+        Rotate180 => 69,
+        HardDrop => 36,
+        SoftDropBeginEnd => 43,
+        GravityStep => 48,
+        HoldBlock => 72,
+        GarbageAdd => 38,
+        SGarbageAdd => 40,
+        RedBarSet => 41,
+        ArrMove => 52,
+        Aux => 84,
+    }
+}
+
+/// Appends `n` as a MIDI variable length quantity: 7 bits per byte, most
+/// significant first, with the top bit set on all but the last byte.
+fn push_vlq(out: &mut Vec<u8>, n: u32) {
+    let mut bytes = vec![(n & 0x7F) as u8];
+    let mut n = n >> 7;
+    while n > 0 {
+        bytes.push((n & 0x7F) as u8 | 0x80);
+        n >>= 7;
+    }
+    out.extend(bytes.iter().rev());
+}
+
+impl JstrisReplay {
+    /// Renders the replay as a MIDI file; see the [module docs](self).
+    pub fn to_midi(&self) -> Vec<u8> {
+        // (tick, is note on, note); sorting puts note offs before note ons at
+        // the same tick so repeated notes are re-struck.
+        let mut notes: Vec<(u32, bool, u8)> = self
+            .iter_absolute()
+            .flat_map(|(input, at)| {
+                let tick = (at - self.metadata.game_start).num_milliseconds() as u32;
+                let note = midi_note(input);
+                [(tick, true, note), (tick + NOTE_LENGTH_MILLIS, false, note)]
+            })
+            .collect();
+        notes.sort_by_key(|&(tick, on, _)| (tick, on));
+
+        let mut track = vec![];
+        // Tempo:
+        track.extend([0x00, 0xFF, 0x51, 0x03]);
+        track.extend(&MICROS_PER_QUARTER.to_be_bytes()[1..]);
+
+        let mut prev = 0;
+        for (tick, on, note) in notes {
+            push_vlq(&mut track, tick - prev);
+            prev = tick;
+
+            let status = if on { 0x90 } else { 0x80 };
+            track.extend([status | CHANNEL, note, VELOCITY]);
+        }
+
+        // End of track:
+        track.extend([0x00, 0xFF, 0x2F, 0x00]);
+
+        let mut out = Vec::with_capacity(22 + track.len());
+        out.extend(b"MThd");
+        out.extend(6u32.to_be_bytes());
+        // Format 0, 1 track:
+        out.extend(0u16.to_be_bytes());
+        out.extend(1u16.to_be_bytes());
+        out.extend(TICKS_PER_QUARTER.to_be_bytes());
+
+        out.extend(b"MTrk");
+        out.extend((track.len() as u32).to_be_bytes());
+        out.extend(track);
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod test_midi {
+    use super::*;
+    use crate::test_util;
+
+    /// The (delta, status) of each event in a track's data.
+    fn track_events(mut data: &[u8]) -> Vec<(u32, u8)> {
+        let mut events = vec![];
+        while !data.is_empty() {
+            let mut delta = 0;
+            loop {
+                let b = data[0];
+                data = &data[1..];
+                delta = (delta << 7) | (b & 0x7F) as u32;
+                if b & 0x80 == 0 {
+                    break;
+                }
+            }
+
+            let status = data[0];
+            let len = match status {
+                // Meta events: type, length, data.
+                0xFF => 3 + data[2] as usize,
+                _ => 3,
+            };
+            events.push((delta, status));
+            data = &data[len..];
+        }
+
+        events
+    }
+
+    #[test]
+    fn vlq() {
+        let encode = |n| {
+            let mut out = vec![];
+            push_vlq(&mut out, n);
+            out
+        };
+
+        assert_eq!(encode(0), [0x00]);
+        assert_eq!(encode(0x7F), [0x7F]);
+        assert_eq!(encode(0x80), [0x81, 0x00]);
+        assert_eq!(encode(15_614), [0xF9, 0x7E]);
+    }
+
+    #[test]
+    fn sample() {
+        let replay = test_util::sample();
+        let midi = replay.to_midi();
+
+        assert_eq!(&midi[..4], b"MThd");
+        assert_eq!(midi[4..14], [0, 0, 0, 6, 0, 0, 0, 1, 0x03, 0xE8]);
+        assert_eq!(&midi[14..18], b"MTrk");
+        let len = u32::from_be_bytes(midi[18..22].try_into().unwrap()) as usize;
+        assert_eq!(midi.len(), 22 + len);
+
+        let events = track_events(&midi[22..]);
+        let count = |status| events.iter().filter(|&&(_, s)| s == status).count();
+        assert_eq!(count(0x90), replay.data.len());
+        assert_eq!(count(0x80), replay.data.len());
+        assert_eq!(events.last(), Some(&(0, 0xFF)));
+
+        // The last note ends `NOTE_LENGTH_MILLIS` after the padding input.
+        let ticks: u32 = events.iter().map(|&(delta, _)| delta).sum();
+        assert_eq!(ticks, 16383 + NOTE_LENGTH_MILLIS);
+    }
+}
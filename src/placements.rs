@@ -0,0 +1,203 @@
+//! Tracking which piece each hard drop placed.
+
+use std::mem;
+
+use chrono::Duration;
+
+use crate::{
+    rng::{JstrisBag, Piece},
+    EventListIter, Input, JstrisReplay,
+};
+
+/// A piece that was hard dropped.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Placement {
+    pub piece: Piece,
+    /// Offset from the start of the game.
+    pub time: Duration,
+}
+
+/// Iterator over the pieces placed in a replay; see
+/// [`JstrisReplay::placements`].
+pub struct Placements<'a> {
+    events: EventListIter<'a>,
+    bag: JstrisBag,
+    current: Piece,
+    // Empty until the first hold; that hold stashes the current piece and
+    // pulls the next one from the queue. Holds after that swap.
+    hold: Option<Piece>,
+    // Only one hold is allowed per piece.
+    can_hold: bool,
+}
+
+impl Iterator for Placements<'_> {
+    type Item = Placement;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (input, time) = self.events.next()?;
+
+            match input {
+                Input::HoldBlock if self.can_hold => {
+                    self.can_hold = false;
+                    self.current = match self.hold.replace(self.current) {
+                        Some(held) => held,
+                        None => self.bag.get(),
+                    };
+                }
+                Input::HardDrop => {
+                    self.can_hold = true;
+                    let piece = mem::replace(&mut self.current, self.bag.get());
+
+                    return Some(Placement { piece, time });
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl JstrisReplay {
+    /// Yields the piece placed by each hard drop, in order.
+    ///
+    /// This only follows the piece queue (and holds); it doesn't simulate the
+    /// board.
+    pub fn placements(&self) -> Placements<'_> {
+        let mut bag = JstrisBag::new(self.metadata.seed.clone());
+        let current = bag.get();
+
+        Placements {
+            events: self.data.iter(),
+            bag,
+            current,
+            hold: None,
+            can_hold: true,
+        }
+    }
+
+    /// The first `n` pieces of the game's queue (ignoring holds).
+    pub fn seed_pieces(&self, n: usize) -> Vec<Piece> {
+        JstrisBag::new(self.metadata.seed.clone())
+            .iter()
+            .take(n)
+            .collect()
+    }
+
+    /// The piece placed by the `index`th (starting at 0) hard drop, if there
+    /// was one.
+    pub fn piece_at_placement(&self, index: usize) -> Option<Piece> {
+        self.placements().nth(index).map(|p| p.piece)
+    }
+
+    /// The pieces placed by the first `n` hard drops (fewer if the game
+    /// didn't have `n`); see [`OpeningFingerprint`].
+    pub fn opening_fingerprint(&self, n: usize) -> OpeningFingerprint {
+        OpeningFingerprint(self.placements().take(n).map(|p| p.piece).collect())
+    }
+}
+
+/// The order a game's first few pieces were placed in (which, because of
+/// holds, isn't always the queue's order); a key for grouping or looking up
+/// games by their opening.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OpeningFingerprint(pub Vec<Piece>);
+
+#[cfg(test)]
+mod test_placements {
+    use super::*;
+    use crate::test_util;
+
+    #[test]
+    fn no_holds() {
+        let events: Vec<_> = (1..=10).map(|i| (Input::HardDrop, i * 100)).collect();
+        let replay = test_util::with_events(&events);
+
+        let mut bag = JstrisBag::new(replay.metadata.seed.clone());
+        for i in 0..10 {
+            assert_eq!(replay.piece_at_placement(i), Some(bag.get()), "placement {i}");
+        }
+        assert_eq!(replay.piece_at_placement(10), None);
+
+        let times: Vec<_> = replay.placements().map(|p| p.time.num_milliseconds()).collect();
+        assert_eq!(times, (1..=10).map(|i| i * 100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn seed_pieces() {
+        let replay = test_util::sample();
+        let mut bag = JstrisBag::new(replay.metadata.seed.clone());
+
+        let pieces = replay.seed_pieces(20);
+        assert_eq!(pieces.len(), 20);
+        for piece in pieces {
+            assert_eq!(piece, bag.get());
+        }
+    }
+
+    #[test]
+    fn leading_hold() {
+        use Input::{HardDrop, HoldBlock};
+
+        let replay = test_util::with_events(&[
+            (HoldBlock, 100),
+            (HardDrop, 200),
+            (HardDrop, 300),
+            (HoldBlock, 400),
+            (HardDrop, 500),
+            (HardDrop, 600),
+        ]);
+
+        let queue: Vec<_> = JstrisBag::new(replay.metadata.seed.clone())
+            .iter()
+            .take(5)
+            .collect();
+        let placed: Vec<_> = replay.placements().map(|p| p.piece).collect();
+
+        // The first hold pulls from the queue; the second swaps the first
+        // piece back out.
+        assert_eq!(placed, [queue[1], queue[2], queue[0], queue[4]]);
+    }
+
+    #[test]
+    fn one_hold_per_piece() {
+        use Input::{HardDrop, HoldBlock};
+
+        let replay = test_util::with_events(&[(HoldBlock, 100), (HoldBlock, 200), (HardDrop, 300)]);
+        let queue: Vec<_> = JstrisBag::new(replay.metadata.seed.clone())
+            .iter()
+            .take(2)
+            .collect();
+
+        assert_eq!(replay.piece_at_placement(0), Some(queue[1]));
+    }
+}
+
+#[cfg(test)]
+mod test_opening_fingerprint {
+    use super::*;
+    use crate::test_util;
+
+    #[test]
+    fn same_opening() {
+        let replay = test_util::sample();
+        // Same seed, same first pieces, but the game stops early.
+        let shorter = JstrisReplay {
+            data: replay.data.truncate_to_events(100),
+            ..replay.clone()
+        };
+
+        let fingerprint = replay.opening_fingerprint(10);
+        assert_eq!(fingerprint, shorter.opening_fingerprint(10));
+        assert_eq!(fingerprint.0.len(), 10);
+
+        let mut other_seed = replay.clone();
+        other_seed.metadata.seed = "asdf".try_into().unwrap();
+        assert_ne!(other_seed.opening_fingerprint(10), fingerprint);
+    }
+
+    #[test]
+    fn short_game() {
+        let replay = test_util::with_events(&[(Input::HardDrop, 100)]);
+        assert_eq!(replay.opening_fingerprint(10).0.len(), 1);
+    }
+}
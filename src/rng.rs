@@ -1,32 +1,51 @@
 //! https://github.com/macmcmeans/aleaPRNG/blob/cf459e9be0d3761af923b07378fcd6ae60c42623/aleaPRNG-1.1.js
+//!
+//! The PRNG has to match jstris' output exactly. It only uses basic IEEE 754
+//! double operations (which Rust never fuses or reorders) and conversions
+//! with defined results, so it's deterministic on every target with IEEE
+//! double arithmetic; the x87 FPU's extended precision (i.e. on i586) is the
+//! exception. The JS `>>> 0` and `| 0` conversions are done as truncations
+//! that are only applied to values in range.
+
+use std::{
+    fmt::{self, Display, Write},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 struct Mash {
-    state: u32,
+    // A double (like in the JS original) rather than an integer: it can go
+    // past 2^32 between characters and it carries a fractional part.
+    state: f64,
 }
 
 impl Mash {
     const fn new() -> Self {
         Mash {
-            state: 4_022_871_197, /* 0xefc8249d */
+            state: 4_022_871_197., /* 0xefc8249d */
         }
     }
 
     fn mash(&mut self, data: &str) -> f64 {
         for b in data.encode_utf16() {
-            self.state += b as u32;
+            self.state += b as f64;
 
-            let mut h: f64 = 0.02519603282416938 * (self.state as f64);
+            // `h >>> 0` in the original; `h` is always in `[0, 2^32)` here.
+            let mut h: f64 = 0.02519603282416938 * self.state;
 
-            self.state = h as u32;
-            h -= self.state as f64;
-            h *= self.state as f64;
-            self.state = h as u32;
-            h -= self.state as f64;
+            self.state = h.trunc();
+            h -= self.state;
+            h *= self.state;
+            self.state = h.trunc();
+            h -= self.state;
 
-            self.state += (h * 4294967296.) as u32; // uh-oh?
+            self.state += h * 4294967296.;
         }
 
-        self.state as f64 * 2.3283064365386963e-10
+        // `n >>> 0`
+        (self.state as u64 as u32) as f64 * 2.3283064365386963e-10
     }
 }
 
@@ -49,6 +68,15 @@ pub struct AleaPrng {
     s2: f64,
 }
 
+/// A snapshot of an [`AleaPrng`]; see [`AleaPrng::state`].
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AleaState {
+    pub c: u32,
+    pub s0: f64,
+    pub s1: f64,
+    pub s2: f64,
+}
+
 impl AleaPrng {
     pub fn new(seeds: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
         let mut masher = Mash::new();
@@ -79,6 +107,7 @@ impl AleaPrng {
 
     pub fn random(&mut self) -> f64 {
         let t = 2091639. * self.s0 + (self.c as f64) * 2.3283064365386963e-10;
+        // `t | 0` in the original; `t` is always in `[0, 2091640)`.
         self.c = t as u32;
 
         self.s0 = self.s1;
@@ -87,6 +116,18 @@ impl AleaPrng {
 
         self.s2
     }
+
+    /// The generator's current state; [`AleaPrng::from_state`] resumes from
+    /// it, producing the same outputs this generator would from here on.
+    pub fn state(&self) -> AleaState {
+        let &Self { c, s0, s1, s2 } = self;
+        AleaState { c, s0, s1, s2 }
+    }
+
+    pub fn from_state(state: AleaState) -> Self {
+        let AleaState { c, s0, s1, s2 } = state;
+        Self { c, s0, s1, s2 }
+    }
 }
 
 #[cfg(test)]
@@ -103,16 +144,141 @@ mod test_prng {
         assert_eq!(rng.random(), 0.5619115477893502);
         assert_eq!(rng.random(), 0.6947485841810703);
     }
+
+    #[test]
+    fn js_cross_check() {
+        // The first 5 outputs and the 1001st, from the JS implementation.
+        let cases: [(&[&str], [f64; 5], f64); 5] = [
+            (
+                &["c07yl8j"],
+                [
+                    0.015538094565272331,
+                    0.7457458288408816,
+                    0.8051643657963723,
+                    0.08473027218133211,
+                    0.05969847971573472,
+                ],
+                0.3985538389533758,
+            ),
+            (
+                &["8bf82p"],
+                [
+                    0.7035422509070486,
+                    0.46214904682710767,
+                    0.2940537005197257,
+                    0.4105223973747343,
+                    0.9704990279860795,
+                ],
+                0.8664122531190515,
+            ),
+            (
+                &["zzzzzzzzzz"],
+                [
+                    0.15689514391124249,
+                    0.3188670058734715,
+                    0.7769196203444153,
+                    0.0023521867115050554,
+                    0.6653745896182954,
+                ],
+                0.505120201734826,
+            ),
+            (
+                &["a", "b"],
+                [
+                    0.5476640737615526,
+                    0.7266150347422808,
+                    0.06857106881216168,
+                    0.5359697500243783,
+                    0.3449200205504894,
+                ],
+                0.4199504100251943,
+            ),
+            (
+                // Mashed as UTF-16 code units, like JS strings.
+                &["\u{fc}n\u{ef}"],
+                [
+                    0.25619982718490064,
+                    0.2646232412662357,
+                    0.8871521367691457,
+                    0.5506251340266317,
+                    0.29186363657936454,
+                ],
+                0.9317784951999784,
+            ),
+        ];
+
+        for (seeds, first, thousand_and_first) in cases {
+            let mut rng = AleaPrng::new(seeds);
+            let got: Vec<_> = (0..5).map(|_| rng.random()).collect();
+            assert_eq!(got, first, "{seeds:?}");
+
+            for _ in 0..995 {
+                rng.random();
+            }
+            assert_eq!(rng.random(), thousand_and_first, "{seeds:?}");
+        }
+    }
+
+    #[test]
+    fn mash_is_stateful() {
+        let mut m = Mash::new();
+        assert_eq!(m.mash("c07yl8j"), 0.7035318505950272);
+        assert_eq!(m.mash("c07yl8j"), 0.15958482376299798);
+    }
+
+    #[test]
+    fn state() {
+        let mut rng = AleaPrng::new(["c07yl8j"]);
+        for _ in 0..100 {
+            rng.random();
+        }
+
+        let json = serde_json::to_string(&rng.state()).unwrap();
+        let mut resumed = AleaPrng::from_state(serde_json::from_str(&json).unwrap());
+        for _ in 0..100 {
+            assert_eq!(resumed.random(), rng.random());
+        }
+    }
+
+    #[test]
+    fn mash_state_past_u32() {
+        // The mash state goes past `u32::MAX` partway through this seed; the
+        // expected values are from the JS implementation.
+        let mut rng = AleaPrng::new(["e3779b90"]);
+
+        assert_eq!(rng.random(), 0.2414791330229491);
+        assert_eq!(rng.random(), 0.12486507487483323);
+    }
 }
 
 type OneBag = ArrayVec<Piece, { FRESH_BAG.len() }>;
 
+/// A game's piece queue.
+///
+/// Drawing pieces advances the PRNG, so the bag needs `&mut` access but it
+/// holds no shared state: it's `Send` and `Sync`, and bags for different
+/// games can be used on separate threads independently.
+#[derive(Clone)]
 pub struct JstrisBag {
     prng: AleaPrng,
     bag: OneBag,
 }
 
-#[derive(Copy, Clone, Debug)]
+/// A snapshot of a [`JstrisBag`]; see [`JstrisBag::state`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BagState {
+    pub prng: AleaState,
+    /// The pieces left in the current bag, in the order they'll be drawn.
+    pub remaining: Vec<Piece>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum BagStateError {
+    #[error("a bag holds at most {} pieces; got {len}", FRESH_BAG.len())]
+    TooManyPieces { len: usize },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[repr(u8)]
 pub enum Piece {
     I,
@@ -124,6 +290,95 @@ pub enum Piece {
     Z,
 }
 
+impl Piece {
+    /// Every piece, in the order of the variants.
+    pub const ALL: [Piece; 7] = [I, J, L, O, S, T, Z];
+
+    /// The piece's letter.
+    pub const fn letter(self) -> char {
+        match self {
+            I => 'I',
+            J => 'J',
+            L => 'L',
+            O => 'O',
+            S => 'S',
+            T => 'T',
+            Z => 'Z',
+        }
+    }
+}
+
+impl Display for Piece {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_char(self.letter())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum PieceParseError {
+    #[error("expected a piece letter; got an empty string")]
+    Empty,
+    #[error("expected a single piece letter; got `{s}`")]
+    TooLong { s: String },
+    #[error("`{c}` is not a piece letter")]
+    InvalidChar { c: char },
+}
+
+/// Parses the piece's (upper case) letter, i.e. what [`Display`] prints.
+impl FromStr for Piece {
+    type Err = PieceParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        let (Some(c), None) = (chars.next(), chars.next()) else {
+            return Err(if s.is_empty() {
+                PieceParseError::Empty
+            } else {
+                PieceParseError::TooLong { s: s.to_string() }
+            });
+        };
+
+        Piece::ALL
+            .into_iter()
+            .find(|p| p.letter() == c)
+            .ok_or(PieceParseError::InvalidChar { c })
+    }
+}
+
+#[cfg(test)]
+mod test_piece {
+    use super::*;
+
+    #[test]
+    fn letters() {
+        let queue: String = Piece::ALL.iter().map(Piece::to_string).collect();
+        assert_eq!(queue, "IJLOSTZ");
+
+        for piece in Piece::ALL {
+            assert_eq!(piece.to_string().parse(), Ok(piece));
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!("".parse::<Piece>(), Err(PieceParseError::Empty));
+        assert_eq!(
+            "i".parse::<Piece>(),
+            Err(PieceParseError::InvalidChar { c: 'i' })
+        );
+        assert_eq!(
+            "X".parse::<Piece>(),
+            Err(PieceParseError::InvalidChar { c: 'X' })
+        );
+        assert_eq!(
+            "IO".parse::<Piece>(),
+            Err(PieceParseError::TooLong {
+                s: "IO".to_string()
+            })
+        );
+    }
+}
+
 use arrayvec::ArrayVec;
 use Piece::*;
 const FRESH_BAG: [Piece; 7] = [I, O, T, L, J, S, Z];
@@ -139,18 +394,70 @@ fn fresh_bag(rng: &mut AleaPrng) -> OneBag {
     array.into_iter().rev().collect()
 }
 
+/// jstris doesn't start games with an S or a Z: if the first bag (`bag`, in
+/// the order the pieces are drawn) starts with one, it's swapped with the
+/// first piece that isn't an S or a Z.
+///
+/// Since S and Z are the only such pieces (and a bag has one of each) that's
+/// either the second or the third piece.
+pub fn apply_first_bag_sz_rule(bag: &mut [Piece]) {
+    use std::mem::swap;
+
+    match bag {
+        [s @ (S | Z), S | Z, other, ..] => swap(s, other),
+        [s @ (S | Z), other, ..] => swap(s, other),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test_first_bag_sz_rule {
+    use super::*;
+
+    fn apply<const N: usize>(mut bag: [Piece; N]) -> [Piece; N] {
+        apply_first_bag_sz_rule(&mut bag);
+        bag
+    }
+
+    #[test]
+    fn s_then_z() {
+        assert_eq!(apply([S, Z, T, I, O, L, J]), [T, Z, S, I, O, L, J]);
+        assert_eq!(apply([Z, S, I, T, O, L, J]), [I, S, Z, T, O, L, J]);
+    }
+
+    #[test]
+    fn s_or_z() {
+        assert_eq!(apply([S, O, Z, T, I, L, J]), [O, S, Z, T, I, L, J]);
+        assert_eq!(apply([Z, J, L, T, I, O, S]), [J, Z, L, T, I, O, S]);
+    }
+
+    #[test]
+    fn no_op() {
+        for bag in [[I, S, Z, O, T, L, J], [T, Z, S, I, O, L, J]] {
+            assert_eq!(apply(bag), bag);
+        }
+        assert_eq!(apply([]), []);
+        assert_eq!(apply([S]), [S]);
+    }
+}
+
 impl JstrisBag {
+    /// jstris seeds its PRNG with the seed string exactly as it appears in the
+    /// replay (no case changes, no padding); so do we.
     pub fn new(seed: crate::GameSeed) -> Self {
-        use std::mem::swap;
+        Self::from_prng(AleaPrng::new([seed.as_str()]))
+    }
 
-        let mut prng = AleaPrng::new([seed]);
+    /// Starts a game's queue from `prng` rather than from a seed; the first
+    /// bag is drawn from `prng` as it is now.
+    pub fn from_prng(mut prng: AleaPrng) -> Self {
         let mut bag = fresh_bag(&mut prng);
 
-        match &mut *bag {
-            [.., other, S | Z, s @ (S | Z)] => swap(s, other),
-            [.., other, s @ (S | Z)] => swap(s, other),
-            _ => {},
-        }
+        // `get` takes pieces from the back of the bag.
+        bag.reverse();
+        apply_first_bag_sz_rule(&mut bag);
+        bag.reverse();
+        debug_assert!(!matches!(bag.last(), Some(S | Z)), "{bag:?}");
 
         Self { prng, bag }
     }
@@ -167,4 +474,167 @@ impl JstrisBag {
     pub fn iter(&mut self) -> impl Iterator<Item = Piece> + '_ {
         std::iter::from_fn(|| Some(self.get()))
     }
+
+    /// The PRNG's state and what's left of the current bag; resuming from
+    /// this with [`JstrisBag::from_state`] gives the same pieces as this bag
+    /// would from here on.
+    pub fn state(&self) -> BagState {
+        BagState {
+            prng: self.prng.state(),
+            remaining: self.bag.iter().rev().copied().collect(),
+        }
+    }
+
+    pub fn from_state(state: BagState) -> Result<Self, BagStateError> {
+        let len = state.remaining.len();
+        if len > FRESH_BAG.len() {
+            return Err(BagStateError::TooManyPieces { len });
+        }
+
+        Ok(Self {
+            prng: AleaPrng::from_state(state.prng),
+            bag: state.remaining.into_iter().rev().collect(),
+        })
+    }
+
+    /// The next `n` pieces, without drawing them: [`get`](Self::get) still
+    /// returns the first of these afterwards.
+    pub fn peek(&self, n: usize) -> Vec<Piece> {
+        // The PRNG is `Copy` so this is cheap: a copy of its state and of (at
+        // most 7 pieces of) the current bag.
+        self.clone().iter().take(n).collect()
+    }
+
+    /// Advances past the next `n` pieces; equivalent to (but cheaper than)
+    /// calling [`get`](Self::get) `n` times.
+    pub fn skip(&mut self, mut n: usize) {
+        loop {
+            let from_this_bag = n.min(self.bag.len());
+            self.bag.truncate(self.bag.len() - from_this_bag);
+            n -= from_this_bag;
+
+            if n == 0 {
+                break;
+            }
+            self.bag = fresh_bag(&mut self.prng);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_bag {
+    use super::*;
+
+    fn bag() -> JstrisBag {
+        JstrisBag::new("c07yl8j".try_into().unwrap())
+    }
+
+    #[test]
+    fn seed_is_used_as_is() {
+        let seed: crate::GameSeed = "c07yl8j".try_into().unwrap();
+
+        let mut from_seed = AleaPrng::new([seed.as_str()]);
+        let mut from_str = AleaPrng::new(["c07yl8j"]);
+        let mut padded = AleaPrng::new(["c07yl8j\0\0\0"]);
+        for _ in 0..10 {
+            let expected = from_str.random();
+            assert_eq!(from_seed.random(), expected);
+            assert_ne!(padded.random(), expected);
+        }
+
+        // And the bag agrees with the first bag of the sample replay (which
+        // clears 40 lines when simulated with it; see `sim`).
+        assert_eq!(
+            bag().iter().take(7).collect::<Vec<_>>(),
+            [I, S, Z, O, T, L, J]
+        );
+    }
+
+    #[test]
+    fn never_starts_with_s_or_z() {
+        for i in 0..1000 {
+            let seed = format!("{:x}", (i * 0x9e37_79b9_u64) as u32);
+            let first = JstrisBag::new(seed.as_str().try_into().unwrap()).get();
+            assert!(!matches!(first, S | Z), "seed {seed} starts with {first:?}");
+        }
+    }
+
+    #[test]
+    fn from_prng() {
+        let mut from_seed = JstrisBag::new("asdf".try_into().unwrap());
+        let mut from_prng = JstrisBag::from_prng(AleaPrng::new(["asdf"]));
+
+        assert_eq!(
+            from_seed.iter().take(50).collect::<Vec<_>>(),
+            from_prng.iter().take(50).collect::<Vec<_>>(),
+        );
+    }
+
+    #[test]
+    fn skip() {
+        let expected: Vec<_> = bag().iter().take(11).collect();
+
+        let mut b = bag();
+        b.skip(10);
+        assert_eq!(b.get(), expected[10]);
+    }
+
+    #[test]
+    fn skip_matches_get() {
+        for n in [0, 1, 6, 7, 8, 13, 14, 15, 50] {
+            let mut skipped = bag();
+            skipped.skip(n);
+
+            let mut stepped = bag();
+            for _ in 0..n {
+                stepped.get();
+            }
+
+            assert_eq!(
+                skipped.iter().take(30).collect::<Vec<_>>(),
+                stepped.iter().take(30).collect::<Vec<_>>(),
+                "n = {n}",
+            );
+        }
+    }
+
+    #[test]
+    fn state() {
+        let mut b = bag();
+        b.skip(10);
+        let state = b.state();
+        assert_eq!(state.remaining, b.peek(4));
+
+        let json = serde_json::to_string(&state).unwrap();
+        let restored: BagState = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, state);
+
+        let mut resumed = JstrisBag::from_state(restored).unwrap();
+        assert_eq!(
+            resumed.iter().take(50).collect::<Vec<_>>(),
+            b.iter().take(50).collect::<Vec<_>>(),
+        );
+
+        let overfull = BagState {
+            remaining: vec![I; 8],
+            ..state
+        };
+        assert_eq!(
+            JstrisBag::from_state(overfull).err(),
+            Some(BagStateError::TooManyPieces { len: 8 })
+        );
+    }
+
+    #[test]
+    fn peek() {
+        let mut b = bag();
+        b.skip(3);
+
+        let peeked = b.peek(20);
+        assert_eq!(peeked.len(), 20);
+        assert_eq!(b.peek(5), peeked[..5]);
+        assert_eq!(b.peek(0), []);
+
+        assert_eq!(b.iter().take(20).collect::<Vec<_>>(), peeked);
+    }
 }
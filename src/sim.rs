@@ -0,0 +1,713 @@
+//! A simulation of the playfield, driven by a replay's inputs.
+//!
+//! This follows the guideline rules jstris uses (SRS rotation and kicks,
+//! one hold per piece) but is not a reimplementation of the jstris client;
+//! in particular:
+//!   - soft drop is treated as instant regardless of the replay's
+//!     [`SoftDropSpeed`](crate::SoftDropSpeed)
+//!   - [`DasLeft`](Input::DasLeft)/[`DasRight`](Input::DasRight) move to the
+//!     wall when the replay's ARR is 0 (or unspecified) and one column
+//!     otherwise, with [`ArrMove`](Input::ArrMove) repeating the last DAS
+//!   - 180 rotations don't kick
+//!   - pieces only lock on [`HardDrop`](Input::HardDrop), never because
+//!     their lock delay ran out
+//!   - garbage is ignored
+//!
+//! T-spins are detected with the 3-corner rule.
+
+use std::mem;
+
+use chrono::Duration;
+
+use crate::{
+    rng::{JstrisBag, Piece},
+    EventListIter, Input, JstrisReplay,
+};
+
+pub const BOARD_WIDTH: usize = 10;
+/// Rows above the visible part of the board that pieces can occupy.
+pub const BUFFER_HEIGHT: usize = 20;
+pub const VISIBLE_HEIGHT: usize = 20;
+pub const BOARD_HEIGHT: usize = BUFFER_HEIGHT + VISIBLE_HEIGHT;
+
+/// The settled blocks on the playfield.
+///
+/// Rows are numbered from the top (like jstris' matrix); the bottom row is
+/// `BOARD_HEIGHT - 1`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Board {
+    rows: [[bool; BOARD_WIDTH]; BOARD_HEIGHT],
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Board {
+    pub const fn new() -> Self {
+        Board {
+            rows: [[false; BOARD_WIDTH]; BOARD_HEIGHT],
+        }
+    }
+
+    /// Whether `(x, y)` has a block in it; out of bounds cells count as
+    /// filled.
+    pub fn is_filled(&self, x: i8, y: i8) -> bool {
+        if x < 0 || y < 0 {
+            return true;
+        }
+
+        self.rows
+            .get(y as usize)
+            .and_then(|row| row.get(x as usize))
+            .copied()
+            .unwrap_or(true)
+    }
+
+    /// The height of the highest block in each column (0 for an empty
+    /// column).
+    pub fn column_heights(&self) -> [u8; BOARD_WIDTH] {
+        std::array::from_fn(|x| {
+            self.rows
+                .iter()
+                .position(|row| row[x])
+                .map_or(0, |y| (BOARD_HEIGHT - y) as u8)
+        })
+    }
+
+    /// The sum of the height differences between neighbouring columns.
+    pub fn bumpiness(&self) -> u32 {
+        self.column_heights()
+            .windows(2)
+            .map(|w| w[0].abs_diff(w[1]) as u32)
+            .sum()
+    }
+
+    /// The number of empty cells that have a block somewhere above them in
+    /// the same column.
+    pub fn holes(&self) -> u32 {
+        let heights = self.column_heights();
+
+        (0..BOARD_WIDTH)
+            .map(|x| {
+                let top = BOARD_HEIGHT - heights[x] as usize;
+                self.rows[top..].iter().filter(|row| !row[x]).count() as u32
+            })
+            .sum()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.rows.iter().flatten().all(|&c| !c)
+    }
+
+    /// Whether `piece`, in `rotation` with the top left of its bounding box
+    /// at `(x, y)`, is within the board and doesn't overlap any blocks.
+    pub fn can_place(&self, piece: Piece, rotation: Rotation, x: i8, y: i8) -> bool {
+        self.fits(&ActivePiece {
+            piece,
+            rotation,
+            x,
+            y,
+        })
+    }
+
+    /// Adds `piece` (positioned like in [`Board::can_place`]) to the board
+    /// and clears any rows that are now full; returns the number of rows
+    /// cleared.
+    ///
+    /// `None` (leaving the board unchanged) if the piece can't be placed
+    /// there. The piece doesn't have to be resting on anything.
+    pub fn lock(&mut self, piece: Piece, rotation: Rotation, x: i8, y: i8) -> Option<u8> {
+        let piece = ActivePiece {
+            piece,
+            rotation,
+            x,
+            y,
+        };
+
+        self.fits(&piece).then(|| self.lock_piece(&piece))
+    }
+
+    /// Where `piece` spawns (the top left of its bounding box, in its spawn
+    /// orientation); `None` if it doesn't fit there, i.e. the game has topped
+    /// out.
+    pub fn spawn_piece(&self, piece: Piece) -> Option<(i8, i8)> {
+        let spawned = ActivePiece::spawn(piece);
+        self.fits(&spawned).then_some((spawned.x, spawned.y))
+    }
+
+    fn fits(&self, piece: &ActivePiece) -> bool {
+        piece.cells().iter().all(|&(x, y)| !self.is_filled(x, y))
+    }
+
+    /// Adds the piece's blocks to the board and clears any rows that are
+    /// now full; returns the number of rows cleared.
+    fn lock_piece(&mut self, piece: &ActivePiece) -> u8 {
+        for (x, y) in piece.cells() {
+            self.rows[y as usize][x as usize] = true;
+        }
+
+        let mut cleared = 0;
+        for y in 0..BOARD_HEIGHT {
+            if self.rows[y].iter().all(|&c| c) {
+                self.rows.copy_within(0..y, 1);
+                self.rows[0] = [false; BOARD_WIDTH];
+                cleared += 1;
+            }
+        }
+
+        cleared
+    }
+
+    /// Whether `piece` is a T-spin, by the 3-corner rule: the piece must be a
+    /// T, at least 3 of the corners of its bounding box must be filled and
+    /// `kick` must be the kick used by a rotation that's the last thing to
+    /// have moved the piece.
+    ///
+    /// It's a [mini](TSpin::Mini) unless both corners the T points towards
+    /// are filled or the rotation used the last kick.
+    fn t_spin(&self, piece: &ActivePiece, kick: usize) -> Option<TSpin> {
+        use Rotation::*;
+
+        if piece.piece != Piece::T {
+            return None;
+        }
+
+        let filled = |(x, y)| self.is_filled(piece.x + x, piece.y + y);
+        let corners = [(0, 0), (2, 0), (0, 2), (2, 2)];
+        if corners.into_iter().filter(|&c| filled(c)).count() < 3 {
+            return None;
+        }
+
+        let front = match piece.rotation {
+            Spawn => [(0, 0), (2, 0)],
+            Right => [(2, 0), (2, 2)],
+            Reverse => [(0, 2), (2, 2)],
+            Left => [(0, 0), (0, 2)],
+        };
+        Some(if front.into_iter().all(filled) || kick == 4 {
+            TSpin::Full
+        } else {
+            TSpin::Mini
+        })
+    }
+}
+
+/// The orientation of a piece.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Rotation {
+    Spawn,
+    Right,
+    Reverse,
+    Left,
+}
+
+impl Rotation {
+    const fn cw(self) -> Self {
+        use Rotation::*;
+
+        match self {
+            Spawn => Right,
+            Right => Reverse,
+            Reverse => Left,
+            Left => Spawn,
+        }
+    }
+
+    const fn index(self) -> usize {
+        self as usize
+    }
+}
+
+// SRS offsets (in the usual x right, y up convention) for each rotation
+// state; the kicks for a rotation are the differences between the offsets of
+// the two states. See: https://tetris.wiki/Super_Rotation_System
+const JLSTZ_OFFSETS: [[(i8, i8); 5]; 4] = [
+    [(0, 0), (0, 0), (0, 0), (0, 0), (0, 0)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    [(0, 0), (0, 0), (0, 0), (0, 0), (0, 0)],
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+];
+const I_OFFSETS: [[(i8, i8); 5]; 4] = [
+    [(0, 0), (-1, 0), (2, 0), (-1, 0), (2, 0)],
+    [(-1, 0), (0, 0), (0, 0), (0, 1), (0, -2)],
+    [(-1, 1), (1, 1), (-2, 1), (1, 0), (-2, 0)],
+    [(0, 1), (0, 1), (0, 1), (0, -1), (0, 2)],
+];
+
+/// Kicks (in board coordinates, i.e. y down) to try when rotating `piece`
+/// from `from` to `to`.
+fn kicks(piece: Piece, from: Rotation, to: Rotation) -> impl Iterator<Item = (i8, i8)> {
+    let offsets = match piece {
+        Piece::I => &I_OFFSETS,
+        _ => &JLSTZ_OFFSETS,
+    };
+    let (from, to) = (offsets[from.index()], offsets[to.index()]);
+
+    // Pieces rotate within their bounding box here (rather than about a
+    // cell) which already accounts for the first offset.
+    let base = (from[0].0 - to[0].0, from[0].1 - to[0].1);
+    (0..5).map(move |i| {
+        let (dx, dy) = (from[i].0 - to[i].0 - base.0, from[i].1 - to[i].1 - base.1);
+        (dx, -dy)
+    })
+}
+
+/// The piece's cells in its spawn orientation, within its bounding box, along
+/// with the size of that box.
+const fn spawn_cells(piece: Piece) -> ([(i8, i8); 4], i8) {
+    use Piece::*;
+
+    match piece {
+        I => ([(0, 1), (1, 1), (2, 1), (3, 1)], 4),
+        J => ([(0, 0), (0, 1), (1, 1), (2, 1)], 3),
+        L => ([(2, 0), (0, 1), (1, 1), (2, 1)], 3),
+        O => ([(0, 0), (1, 0), (0, 1), (1, 1)], 2),
+        S => ([(1, 0), (2, 0), (0, 1), (1, 1)], 3),
+        T => ([(1, 0), (0, 1), (1, 1), (2, 1)], 3),
+        Z => ([(0, 0), (1, 0), (1, 1), (2, 1)], 3),
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+struct ActivePiece {
+    piece: Piece,
+    rotation: Rotation,
+    /// The top left of the piece's bounding box.
+    x: i8,
+    y: i8,
+}
+
+impl ActivePiece {
+    fn spawn(piece: Piece) -> Self {
+        let (_, size) = spawn_cells(piece);
+
+        ActivePiece {
+            piece,
+            rotation: Rotation::Spawn,
+            x: (BOARD_WIDTH as i8 - size) / 2,
+            y: BUFFER_HEIGHT as i8 - 2,
+        }
+    }
+
+    fn cells(&self) -> [(i8, i8); 4] {
+        let (mut cells, size) = spawn_cells(self.piece);
+
+        let mut r = Rotation::Spawn;
+        while r != self.rotation {
+            for (x, y) in &mut cells {
+                (*x, *y) = (size - 1 - *y, *x);
+            }
+            r = r.cw();
+        }
+
+        cells.map(|(x, y)| (self.x + x, self.y + y))
+    }
+
+    fn shifted(self, dx: i8, dy: i8) -> Self {
+        ActivePiece {
+            x: self.x + dx,
+            y: self.y + dy,
+            ..self
+        }
+    }
+}
+
+/// A T-spin; see the [module docs](self).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TSpin {
+    Mini,
+    Full,
+}
+
+/// A piece locking into place during a [`Simulation`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Lock {
+    pub piece: Piece,
+    /// Offset from the start of the game.
+    pub time: Duration,
+    pub lines_cleared: u8,
+    pub t_spin: Option<TSpin>,
+    /// Whether the board was left empty.
+    pub perfect_clear: bool,
+}
+
+/// Plays a replay's inputs on a [`Board`], yielding each piece as it locks;
+/// see [`JstrisReplay::simulate`].
+///
+/// Ends early if the game tops out.
+pub struct Simulation<'a> {
+    events: EventListIter<'a>,
+    bag: JstrisBag,
+    board: Board,
+    current: ActivePiece,
+    // Same as in `Placements`.
+    hold: Option<Piece>,
+    can_hold: bool,
+    arr_is_instant: bool,
+    last_das: i8,
+    soft_dropping: bool,
+    topped_out: bool,
+    // The kick used by the last rotation; cleared when the piece moves.
+    last_kick: Option<usize>,
+}
+
+impl Simulation<'_> {
+    /// The settled blocks, not including the piece that's in play.
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    fn try_move(&mut self, dx: i8, dy: i8) -> bool {
+        let moved = self.current.shifted(dx, dy);
+        let fits = self.board.fits(&moved);
+        if fits {
+            self.current = moved;
+            self.last_kick = None;
+        }
+
+        fits
+    }
+
+    fn rotate(&mut self, to: Rotation) {
+        let rotated = ActivePiece {
+            rotation: to,
+            ..self.current
+        };
+
+        // No kicks for 180s.
+        let kicks: Vec<_> = if to == self.current.rotation.cw().cw() {
+            vec![(0, 0)]
+        } else {
+            kicks(self.current.piece, self.current.rotation, to).collect()
+        };
+        if let Some((kick, kicked)) = kicks
+            .into_iter()
+            .map(|(dx, dy)| rotated.shifted(dx, dy))
+            .enumerate()
+            .find(|(_, p)| self.board.fits(p))
+        {
+            self.current = kicked;
+            self.last_kick = Some(kick);
+        }
+    }
+
+    fn spawn(&mut self, piece: Piece) {
+        self.current = ActivePiece::spawn(piece);
+        self.last_kick = None;
+        self.topped_out = !self.board.fits(&self.current);
+    }
+}
+
+impl Iterator for Simulation<'_> {
+    type Item = Lock;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        use Input::*;
+
+        while !self.topped_out {
+            let (input, time) = self.events.next()?;
+
+            match input {
+                MoveLeft => {
+                    self.try_move(-1, 0);
+                }
+                MoveRight => {
+                    self.try_move(1, 0);
+                }
+                DasLeft | DasRight => {
+                    self.last_das = if input == DasLeft { -1 } else { 1 };
+                    while self.try_move(self.last_das, 0) && self.arr_is_instant {}
+                }
+                ArrMove => {
+                    self.try_move(self.last_das, 0);
+                }
+                RotateLeft => self.rotate(self.current.rotation.cw().cw().cw()),
+                RotateRight => self.rotate(self.current.rotation.cw()),
+                Rotate180 => self.rotate(self.current.rotation.cw().cw()),
+                SoftDropBeginEnd => self.soft_dropping = !self.soft_dropping,
+                GravityStep => {
+                    self.try_move(0, 1);
+                }
+                HoldBlock if self.can_hold => {
+                    self.can_hold = false;
+                    let next = match self.hold.replace(self.current.piece) {
+                        Some(held) => held,
+                        None => self.bag.get(),
+                    };
+                    self.spawn(next);
+                }
+                HardDrop => {
+                    while self.try_move(0, 1) {}
+
+                    let piece = self.current.piece;
+                    let t_spin = self
+                        .last_kick
+                        .and_then(|kick| self.board.t_spin(&self.current, kick));
+                    let lines_cleared = self
+                        .board
+                        .lock_piece(&mem::replace(&mut self.current, ActivePiece::spawn(piece)));
+
+                    self.can_hold = true;
+                    let next = self.bag.get();
+                    self.spawn(next);
+
+                    return Some(Lock {
+                        piece,
+                        time,
+                        lines_cleared,
+                        t_spin,
+                        perfect_clear: lines_cleared > 0 && self.board.is_empty(),
+                    });
+                }
+                HoldBlock | GarbageAdd | SGarbageAdd | RedBarSet | Aux => {}
+            }
+
+            if self.soft_dropping {
+                while self.try_move(0, 1) {}
+            }
+        }
+
+        None
+    }
+}
+
+impl JstrisReplay {
+    /// Plays the replay's inputs out on an initially empty board; see the
+    /// [module docs](self) for what is and isn't simulated.
+    pub fn simulate(&self) -> Simulation<'_> {
+        let mut bag = JstrisBag::new(self.metadata.seed.clone());
+        let current = ActivePiece::spawn(bag.get());
+
+        Simulation {
+            events: self.data.iter(),
+            bag,
+            board: Board::new(),
+            current,
+            hold: None,
+            can_hold: true,
+            arr_is_instant: self.uses_instant_arr(),
+            last_das: 0,
+            soft_dropping: false,
+            topped_out: false,
+            last_kick: None,
+        }
+    }
+
+    /// When each line total was reached, i.e. split times for sprint modes.
+    ///
+    /// Has an entry for every line cleared: clearing several lines at once
+    /// yields one entry per line, all with the same time.
+    pub fn line_splits(&self) -> Vec<(u32, Duration)> {
+        self.simulate()
+            .flat_map(|lock| std::iter::repeat_n(lock.time, lock.lines_cleared as usize))
+            .enumerate()
+            .map(|(i, time)| (i as u32 + 1, time))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test_board {
+    use super::*;
+
+    /// A board whose bottom rows are `rows` (`#` is a block).
+    fn board(rows: &[&str]) -> Board {
+        let mut board = Board::new();
+        for (row, y) in rows.iter().rev().zip((0..BOARD_HEIGHT).rev()) {
+            for (x, c) in row.chars().enumerate() {
+                board.rows[y][x] = c == '#';
+            }
+        }
+
+        board
+    }
+
+    #[test]
+    fn empty() {
+        let board = Board::new();
+        assert_eq!(board.column_heights(), [0; BOARD_WIDTH]);
+        assert_eq!(board.bumpiness(), 0);
+        assert_eq!(board.holes(), 0);
+    }
+
+    #[test]
+    fn flat() {
+        let board = board(&["##########", "##########"]);
+        assert_eq!(board.column_heights(), [2; BOARD_WIDTH]);
+        assert_eq!(board.bumpiness(), 0);
+        assert_eq!(board.holes(), 0);
+    }
+
+    #[test]
+    fn walls() {
+        let board = Board::new();
+        let bottom = BOARD_HEIGHT as i8 - 2;
+
+        for piece in Piece::ALL {
+            let (_, size) = spawn_cells(piece);
+            let spawn = Rotation::Spawn;
+
+            // Every piece's spawn orientation touches the left and right
+            // edges of its bounding box and has its lowest blocks in its
+            // second row.
+            assert!(board.can_place(piece, spawn, 0, bottom), "{piece:?}");
+            assert!(!board.can_place(piece, spawn, -1, bottom), "{piece:?}");
+            assert!(
+                board.can_place(piece, spawn, 10 - size, bottom),
+                "{piece:?}"
+            );
+            assert!(
+                !board.can_place(piece, spawn, 11 - size, bottom),
+                "{piece:?}"
+            );
+            assert!(!board.can_place(piece, spawn, 0, bottom + 1), "{piece:?}");
+            assert!(!board.can_place(piece, spawn, 0, -2), "{piece:?}");
+
+            assert_eq!(board.spawn_piece(piece), Some(((10 - size) / 2, 18)));
+        }
+    }
+
+    #[test]
+    fn occupied_cell() {
+        let mut board = board(&["....#....."]);
+        let bottom = BOARD_HEIGHT as i8 - 2;
+
+        // A T in its spawn orientation with its stem over the block.
+        assert!(!board.can_place(Piece::T, Rotation::Spawn, 3, bottom));
+        assert!(board.can_place(Piece::T, Rotation::Spawn, 3, bottom - 1));
+        assert_eq!(board.lock(Piece::T, Rotation::Spawn, 3, bottom), None);
+
+        assert_eq!(
+            board.lock(Piece::T, Rotation::Spawn, 3, bottom - 1),
+            Some(0)
+        );
+        assert_eq!(board.column_heights(), [0, 0, 0, 2, 3, 2, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn lock_clears_lines() {
+        let mut board = board(&["..........", "#########.", "#########."]);
+        let top = BOARD_HEIGHT as i8 - 4;
+
+        // A vertical I down the right wall; it sits in the third column of
+        // its bounding box.
+        assert_eq!(board.lock(Piece::I, Rotation::Right, 7, top + 1), None);
+        assert_eq!(board.lock(Piece::I, Rotation::Right, 7, top), Some(2));
+        assert_eq!(board.column_heights(), [0, 0, 0, 0, 0, 0, 0, 0, 0, 2]);
+    }
+
+    #[test]
+    fn t_spins() {
+        let t = |rotation, x, y| ActivePiece {
+            piece: Piece::T,
+            rotation,
+            x,
+            y,
+        };
+        let bottom = BOARD_HEIGHT as i8 - 3;
+
+        // A T-spin double slot, with the T pointing down into it.
+        let tsd = board(&["##........", "#...######", "##.#######"]);
+        assert_eq!(
+            tsd.t_spin(&t(Rotation::Reverse, 1, bottom), 0),
+            Some(TSpin::Full)
+        );
+        // Only one corner under the T is filled.
+        let open = board(&["..........", "#...######", "##.#######"]);
+        assert_eq!(open.t_spin(&t(Rotation::Reverse, 1, bottom), 0), None);
+
+        // Only one of the corners the T points towards is filled.
+        let mini = board(&["#.........", "#..#######", "#.########"]);
+        let piece = t(Rotation::Right, 0, bottom);
+        assert_eq!(mini.t_spin(&piece, 0), Some(TSpin::Mini));
+        // ...unless it got there with the last kick.
+        assert_eq!(mini.t_spin(&piece, 4), Some(TSpin::Full));
+
+        let not_t = ActivePiece {
+            piece: Piece::L,
+            ..t(Rotation::Reverse, 1, bottom)
+        };
+        assert_eq!(tsd.t_spin(&not_t, 0), None);
+    }
+
+    #[test]
+    fn metrics() {
+        let board = board(&[
+            "#.........",
+            "#..#......",
+            "#.##...#..",
+            "####.#.#.#",
+            "#.##.##.##",
+        ]);
+
+        assert_eq!(board.column_heights(), [5, 2, 3, 4, 0, 2, 1, 3, 1, 2]);
+        // 3 + 1 + 1 + 4 + 2 + 1 + 2 + 2 + 1
+        assert_eq!(board.bumpiness(), 17);
+        // At the bottom of columns 1 and 7; column 4 is empty, not a hole.
+        assert_eq!(board.holes(), 2);
+    }
+}
+
+#[cfg(test)]
+mod test_sim {
+    use super::*;
+    use crate::test_util;
+
+    #[test]
+    fn rotation() {
+        let t = |rotation| {
+            let mut cells = ActivePiece {
+                piece: Piece::T,
+                rotation,
+                x: 0,
+                y: 0,
+            }
+            .cells();
+            cells.sort();
+            cells
+        };
+
+        assert_eq!(t(Rotation::Spawn), [(0, 1), (1, 0), (1, 1), (2, 1)]);
+        assert_eq!(t(Rotation::Right), [(1, 0), (1, 1), (1, 2), (2, 1)]);
+        assert_eq!(t(Rotation::Reverse), [(0, 1), (1, 1), (1, 2), (2, 1)]);
+        assert_eq!(t(Rotation::Left), [(0, 1), (1, 0), (1, 1), (1, 2)]);
+    }
+
+    #[test]
+    fn srs_kicks() {
+        // https://tetris.wiki/Super_Rotation_System#Wall_Kicks (with y
+        // flipped)
+        let i: Vec<_> = kicks(Piece::I, Rotation::Spawn, Rotation::Right).collect();
+        assert_eq!(i, [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)]);
+
+        let t: Vec<_> = kicks(Piece::T, Rotation::Left, Rotation::Spawn).collect();
+        assert_eq!(t, [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)]);
+    }
+
+    #[test]
+    fn sample_clears_40_lines() {
+        let replay = test_util::sample();
+
+        let lines: u32 = replay.simulate().map(|l| l.lines_cleared as u32).sum();
+        assert_eq!(lines, 40);
+        assert_eq!(replay.simulate().count(), replay.placements().count());
+        assert!(replay.simulate().all(|l| l.t_spin.is_none()));
+    }
+
+    #[test]
+    fn line_splits() {
+        let replay = test_util::sample();
+        let splits = replay.line_splits();
+
+        assert_eq!(splits.len(), 40);
+        assert!(splits
+            .iter()
+            .enumerate()
+            .all(|(i, &(line, _))| line == i as u32 + 1));
+        assert!(splits.windows(2).all(|w| w[0].1 <= w[1].1));
+        assert_eq!(splits.last().unwrap().1, replay.time());
+    }
+}
@@ -0,0 +1,234 @@
+//! Where the CLI gets replays from.
+
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fs::File,
+    io::{self, BufRead, BufReader},
+};
+
+use jstris_replay_re::{decode_uri_string, leaderboard::LeaderboardIter, GameMode, JstrisReplay};
+
+pub type SourceError = Box<dyn Error + 'static>;
+
+/// Something that produces replays, one at a time.
+pub trait ReplaySource {
+    /// The next replay along with a name to refer to it by, or `None` once
+    /// the source is exhausted.
+    async fn next_replay(&mut self) -> Result<Option<(String, JstrisReplay)>, SourceError>;
+}
+
+async fn fetch_replay(replay_id: u32) -> Result<JstrisReplay, SourceError> {
+    println!("fetching replay: {replay_id}...");
+    Ok(jstris_replay_re::fetch::fetch_replay(replay_id).await?)
+}
+
+/// Replays from the 40L sprint leaderboard, best first.
+pub struct LeaderboardSource {
+    leaderboard: LeaderboardIter,
+}
+
+impl LeaderboardSource {
+    pub fn new() -> Self {
+        Self {
+            leaderboard: LeaderboardIter::new(GameMode::_40Line).unwrap(),
+        }
+    }
+}
+
+impl ReplaySource for LeaderboardSource {
+    async fn next_replay(&mut self) -> Result<Option<(String, JstrisReplay)>, SourceError> {
+        let Some(entry) = self.leaderboard.next().await? else {
+            return Ok(None);
+        };
+        let name = format!("replay:{}", entry.replay_id);
+
+        Ok(Some((name, fetch_replay(entry.replay_id).await?)))
+    }
+}
+
+/// Replays fetched from jstris by id.
+pub struct ReplayIdSource {
+    ids: VecDeque<u32>,
+}
+
+impl ReplayIdSource {
+    pub fn new(ids: impl IntoIterator<Item = u32>) -> Self {
+        Self {
+            ids: ids.into_iter().collect(),
+        }
+    }
+}
+
+impl ReplaySource for ReplayIdSource {
+    async fn next_replay(&mut self) -> Result<Option<(String, JstrisReplay)>, SourceError> {
+        let Some(id) = self.ids.pop_front() else {
+            return Ok(None);
+        };
+
+        Ok(Some((format!("replay:{id}"), fetch_replay(id).await?)))
+    }
+}
+
+/// Replays read from local files; each file is expected to have a URI
+/// encoded replay on its first line.
+pub struct FileSource {
+    paths: VecDeque<String>,
+}
+
+impl FileSource {
+    pub fn new(paths: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            paths: paths.into_iter().collect(),
+        }
+    }
+}
+
+impl ReplaySource for FileSource {
+    async fn next_replay(&mut self) -> Result<Option<(String, JstrisReplay)>, SourceError> {
+        let Some(path) = self.paths.pop_front() else {
+            return Ok(None);
+        };
+
+        let f = File::open(&path)?;
+        let mut f = BufReader::new(f);
+        let mut s = String::new();
+        f.read_line(&mut s)?;
+
+        let replay = decode_uri_string(s.as_bytes())?;
+        Ok(Some((path, replay)))
+    }
+}
+
+/// Replays read from stdin, one URI encoded replay per line.
+pub struct StdinSource {
+    line: usize,
+}
+
+impl StdinSource {
+    pub fn new() -> Self {
+        Self { line: 0 }
+    }
+}
+
+impl ReplaySource for StdinSource {
+    async fn next_replay(&mut self) -> Result<Option<(String, JstrisReplay)>, SourceError> {
+        let mut s = String::new();
+        loop {
+            s.clear();
+            if io::stdin().lock().read_line(&mut s)? == 0 {
+                return Ok(None);
+            }
+            self.line += 1;
+
+            if !s.trim().is_empty() {
+                break;
+            }
+        }
+
+        let replay = decode_uri_string(s.as_bytes())?;
+        Ok(Some((format!("stdin:{}", self.line), replay)))
+    }
+}
+
+/// Replays named by command line arguments: `replay:<id>` fetches a replay
+/// from jstris, `-` reads replays from stdin and anything else is treated as
+/// a path to a file.
+pub struct ArgsSource {
+    args: VecDeque<String>,
+    stdin: Option<StdinSource>,
+}
+
+impl ArgsSource {
+    pub fn new(args: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            args: args.into_iter().collect(),
+            stdin: None,
+        }
+    }
+}
+
+impl ReplaySource for ArgsSource {
+    async fn next_replay(&mut self) -> Result<Option<(String, JstrisReplay)>, SourceError> {
+        loop {
+            if let Some(stdin) = &mut self.stdin {
+                match stdin.next_replay().await? {
+                    Some(next) => return Ok(Some(next)),
+                    None => self.stdin = None,
+                }
+            }
+
+            let Some(arg) = self.args.pop_front() else {
+                return Ok(None);
+            };
+
+            if arg == "-" {
+                self.stdin = Some(StdinSource::new());
+            } else if let Some(replay_id) = arg.strip_prefix("replay:") {
+                let replay_id = replay_id.parse()?;
+                return ReplayIdSource::new([replay_id]).next_replay().await;
+            } else {
+                return FileSource::new([arg]).next_replay().await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_sources {
+    use super::*;
+
+    /// Replays that are already in memory.
+    struct InMemorySource {
+        replays: VecDeque<(String, JstrisReplay)>,
+    }
+
+    impl ReplaySource for InMemorySource {
+        async fn next_replay(&mut self) -> Result<Option<(String, JstrisReplay)>, SourceError> {
+            Ok(self.replays.pop_front())
+        }
+    }
+
+    async fn drain(mut source: impl ReplaySource) -> Vec<(String, JstrisReplay)> {
+        let mut out = vec![];
+        while let Some(next) = source.next_replay().await.unwrap() {
+            out.push(next);
+        }
+        out
+    }
+
+    #[tokio::test]
+    async fn in_memory() {
+        let replay = jstris_replay_re::decode_json(crate::SAMPLE_REPLAY_JSON).unwrap();
+        let source = InMemorySource {
+            replays: [
+                ("a".to_string(), replay.clone()),
+                ("b".to_string(), replay.clone()),
+            ]
+            .into(),
+        };
+
+        let replays = drain(source).await;
+        assert_eq!(replays.len(), 2);
+        assert_eq!(replays[0].0, "a");
+        assert_eq!(replays[1].0, "b");
+        assert_eq!(replays[1].1, replay);
+    }
+
+    #[tokio::test]
+    async fn files() {
+        let replay = jstris_replay_re::decode_json(crate::SAMPLE_REPLAY_JSON).unwrap();
+        let encoded = jstris_replay_re::encode_uri_string(&replay).unwrap();
+
+        let path =
+            std::env::temp_dir().join(format!("jstris-replay-source-{}", std::process::id()));
+        std::fs::write(&path, format!("{encoded}\n")).unwrap();
+        let path = path.to_str().unwrap().to_string();
+
+        let replays = drain(ArgsSource::new([path.clone(), path.clone()])).await;
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(replays.len(), 2);
+        assert_eq!(replays[0], (path, replay));
+    }
+}
@@ -0,0 +1,379 @@
+//! Statistics derived from a replay's inputs.
+
+use std::collections::BTreeMap;
+
+use chrono::Duration;
+
+use crate::{encode_uri_string, EventList, Input, JstrisReplay};
+
+impl JstrisReplay {
+    /// The number of player inputs (i.e. not [system](crate::Input::is_system)
+    /// events) in each second of the game.
+    ///
+    /// The last entry covers the final, possibly partial, second. Events
+    /// after the end of the game are not counted.
+    pub fn input_rate_per_second(&self) -> Vec<u32> {
+        let length = self.time().num_milliseconds().max(0);
+        let mut bins = vec![0; ((length + 999) / 1000) as usize];
+
+        for (input, time) in self.iter_absolute() {
+            let offset = (time - self.metadata.game_start).num_milliseconds();
+            if input.is_system() || offset > length {
+                continue;
+            }
+
+            // An event right at the end of a game that's a whole number of
+            // seconds long goes in the last bin.
+            let idx = ((offset / 1000) as usize).min(bins.len().saturating_sub(1));
+            if let Some(bin) = bins.get_mut(idx) {
+                *bin += 1;
+            }
+        }
+
+        bins
+    }
+}
+
+#[cfg(test)]
+mod test_input_rate {
+    use chrono::Duration;
+
+    use crate::{test_util, Input::*, Metadata};
+
+    #[test]
+    fn per_second() {
+        let mut replay = test_util::with_events(&[
+            (MoveLeft, 100),
+            (HardDrop, 900),
+            (GravityStep, 950),
+            // nothing in the second second
+            (RotateLeft, 2000),
+            (RotateRight, 2500),
+            (HardDrop, 2999),
+            (HardDrop, 3200),
+        ]);
+        replay.metadata = Metadata {
+            game_end: replay.metadata.game_start + Duration::milliseconds(3200),
+            ..replay.metadata
+        };
+
+        assert_eq!(replay.input_rate_per_second(), [2, 0, 3, 1]);
+    }
+
+    #[test]
+    fn whole_seconds() {
+        let mut replay = test_util::with_events(&[(HardDrop, 500), (HardDrop, 2000)]);
+        replay.metadata.game_end = replay.metadata.game_start + Duration::milliseconds(2000);
+
+        assert_eq!(replay.input_rate_per_second(), [1, 1]);
+    }
+
+    #[test]
+    fn sample() {
+        let replay = test_util::sample();
+        let rates = replay.input_rate_per_second();
+
+        assert_eq!(rates.len(), 16);
+        // Everything but the padding event at the end.
+        assert_eq!(rates.iter().sum::<u32>() as usize, replay.data.len() - 1);
+    }
+}
+
+/// Summary statistics for the gaps between occurrences of an input; see
+/// [`JstrisReplay::input_interval_stats`]. All times are in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IntervalStats {
+    /// The number of gaps (i.e. one less than the number of occurrences).
+    pub count: usize,
+    pub mean: f64,
+    pub median: f64,
+    /// Population standard deviation.
+    pub stddev: f64,
+}
+
+impl JstrisReplay {
+    /// How regularly `input` was used: statistics for the time between each
+    /// occurrence of `input` and the next.
+    ///
+    /// `None` if `input` occurs fewer than two times.
+    pub fn input_interval_stats(&self, input: Input) -> Option<IntervalStats> {
+        let times: Vec<_> = self
+            .iter_absolute()
+            .filter(|&(i, _)| i == input)
+            .map(|(_, t)| t)
+            .collect();
+        let mut gaps: Vec<f64> = times
+            .windows(2)
+            .map(|w| (w[1] - w[0]).num_milliseconds() as f64)
+            .collect();
+        if gaps.is_empty() {
+            return None;
+        }
+
+        gaps.sort_by(f64::total_cmp);
+        let count = gaps.len();
+        let mean = gaps.iter().sum::<f64>() / count as f64;
+        let median = if count.is_multiple_of(2) {
+            (gaps[count / 2 - 1] + gaps[count / 2]) / 2.
+        } else {
+            gaps[count / 2]
+        };
+        let variance = gaps.iter().map(|g| (g - mean).powi(2)).sum::<f64>() / count as f64;
+
+        Some(IntervalStats {
+            count,
+            mean,
+            median,
+            stddev: variance.sqrt(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test_interval_stats {
+    use crate::{test_util, Input::*};
+
+    #[test]
+    fn even_spacing() {
+        let events: Vec<_> = (1..=20)
+            .flat_map(|i| [(MoveLeft, i * 250 - 50), (HardDrop, i * 250)])
+            .collect();
+        let replay = test_util::with_events(&events);
+
+        let stats = replay.input_interval_stats(HardDrop).unwrap();
+        assert_eq!(stats.count, 19);
+        assert_eq!(stats.mean, 250.);
+        assert_eq!(stats.median, 250.);
+        assert!(stats.stddev < 1e-9, "{stats:?}");
+    }
+
+    #[test]
+    fn uneven_spacing() {
+        let replay = test_util::with_events(&[
+            (HardDrop, 0),
+            (HardDrop, 100),
+            (HardDrop, 400),
+            (HardDrop, 500),
+        ]);
+
+        let stats = replay.input_interval_stats(HardDrop).unwrap();
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.median, 100.);
+        assert!((stats.mean - 500. / 3.).abs() < 1e-9);
+        assert!((stats.stddev - 94.28).abs() < 0.01, "{stats:?}");
+    }
+
+    #[test]
+    fn too_few() {
+        let replay = test_util::with_events(&[(HardDrop, 100), (MoveLeft, 200)]);
+
+        assert_eq!(replay.input_interval_stats(HardDrop), None);
+        assert_eq!(replay.input_interval_stats(RotateLeft), None);
+    }
+}
+
+impl JstrisReplay {
+    /// Pieces per second over a trailing `window`, sampled at each placement.
+    ///
+    /// Each entry is a placement's offset from the start of the game and the
+    /// number of pieces placed in the `window` ending at (and including) that
+    /// placement, divided by the window's length. Early in the game the window
+    /// reaches back before the start, so the first few entries read low.
+    ///
+    /// Empty if `window` isn't positive.
+    pub fn sliding_pps(&self, window: Duration) -> Vec<(Duration, f64)> {
+        if window <= Duration::zero() {
+            return vec![];
+        }
+
+        let times: Vec<_> = self.placements().map(|p| p.time).collect();
+        let seconds = window.num_milliseconds() as f64 / 1000.;
+
+        let mut start = 0;
+        times
+            .iter()
+            .enumerate()
+            .map(|(end, &time)| {
+                while times[start] <= time - window {
+                    start += 1;
+                }
+                (time, (end - start + 1) as f64 / seconds)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test_sliding_pps {
+    use chrono::Duration;
+
+    use crate::{test_util, Input::*};
+
+    #[test]
+    fn burst() {
+        // A piece every 500ms, with ten pieces 50ms apart from 5s.
+        let mut events: Vec<_> = (1..=10).map(|i| (HardDrop, i * 500)).collect();
+        events.extend((1..=10).map(|i| (HardDrop, 5000 + i * 50)));
+        events.extend((1..=10).map(|i| (HardDrop, 5500 + i * 500)));
+        let replay = test_util::with_events(&events);
+
+        let pps = replay.sliding_pps(Duration::seconds(1));
+        assert_eq!(pps.len(), 30);
+
+        let at = |ms| {
+            pps.iter()
+                .find(|(t, _)| *t == Duration::milliseconds(ms))
+                .unwrap()
+                .1
+        };
+        assert_eq!(at(4500), 2.);
+        assert_eq!(at(5500), 11.);
+        assert_eq!(at(10000), 2.);
+        assert!(pps.iter().all(|&(_, p)| p <= 11.));
+    }
+
+    #[test]
+    fn empty_window() {
+        assert_eq!(test_util::sample().sliding_pps(Duration::zero()), []);
+    }
+}
+
+/// How big a replay is in each of its encodings; see
+/// [`JstrisReplay::size_report`]. All sizes are in bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SizeReport {
+    /// The replay as JSON (with the event data base64 encoded).
+    pub json_bytes: usize,
+    /// The URI encoded LZ string (what's uploaded to jstris).
+    pub lz_bytes: usize,
+    /// The packed event data on its own.
+    pub event_bytes: usize,
+    /// `json_bytes / lz_bytes`; below 1 when the LZ string is bigger than the
+    /// JSON, which is typical (the base64 event data doesn't compress well).
+    pub compression_ratio: f64,
+}
+
+impl JstrisReplay {
+    /// The size of the replay's JSON, LZ string and packed event encodings.
+    pub fn size_report(&self) -> Result<SizeReport, serde_json::Error> {
+        let json_bytes = serde_json::to_string(self)?.len();
+        // The URI alphabet is all ASCII.
+        let lz_bytes = encode_uri_string(self)?.len();
+
+        Ok(SizeReport {
+            json_bytes,
+            lz_bytes,
+            event_bytes: self.data.encode().len(),
+            compression_ratio: json_bytes as f64 / lz_bytes as f64,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test_size_report {
+    use crate::{encode_uri_string, test_util};
+
+    #[test]
+    fn sample() {
+        let replay = test_util::sample();
+        let report = replay.size_report().unwrap();
+
+        // 334 events (including the padding word), 2 bytes each.
+        assert_eq!(report.event_bytes, 668);
+        assert_eq!(report.lz_bytes, encode_uri_string(&replay).unwrap().len());
+        // The JSON has the events in base64, which is bigger.
+        assert!(report.json_bytes > report.event_bytes * 4 / 3);
+        // The base64 event data doesn't compress, so the LZ string is
+        // actually bigger than the JSON.
+        assert!(report.lz_bytes > report.json_bytes);
+        assert_eq!(
+            report.compression_ratio,
+            report.json_bytes as f64 / report.lz_bytes as f64
+        );
+    }
+}
+
+/// Common metrics for an [`EventList`], gathered in one pass; see
+/// [`EventList::stats`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventStats {
+    pub num_events: usize,
+    /// The offset of the last event; see [`EventList::total_duration`].
+    pub total_duration: Duration,
+    /// How many times each input occurs, indexed by [`Input::code`].
+    pub input_counts: [usize; Input::ALL.len()],
+    /// How many consecutive pairs of events are each gap apart. Divide by the
+    /// frame length to get frame delays.
+    pub gaps: BTreeMap<Duration, usize>,
+}
+
+impl EventStats {
+    /// The number of times `input` occurs.
+    pub fn count(&self, input: Input) -> usize {
+        self.input_counts[input.code() as usize]
+    }
+}
+
+impl EventList {
+    /// [`EventStats`] for the list.
+    pub fn stats(&self) -> EventStats {
+        let mut stats = EventStats {
+            num_events: 0,
+            total_duration: Duration::zero(),
+            input_counts: [0; Input::ALL.len()],
+            gaps: BTreeMap::new(),
+        };
+
+        let mut prev = None;
+        for (input, offset) in self.iter() {
+            stats.num_events += 1;
+            stats.input_counts[input.code() as usize] += 1;
+            if let Some(prev) = prev {
+                *stats.gaps.entry(offset - prev).or_default() += 1;
+            }
+
+            stats.total_duration = offset;
+            prev = Some(offset);
+        }
+
+        stats
+    }
+}
+
+#[cfg(test)]
+mod test_event_stats {
+    use chrono::Duration;
+
+    use crate::{test_util, Input};
+
+    #[test]
+    fn matches_individual_computations() {
+        let events = test_util::sample().data;
+        let stats = events.stats();
+
+        assert_eq!(stats.num_events, events.len());
+        assert_eq!(stats.total_duration, events.total_duration());
+        for input in Input::ALL {
+            let count = events.iter().filter(|&(i, _)| i == input).count();
+            assert_eq!(stats.count(input), count, "{input:?}");
+        }
+
+        let offsets: Vec<_> = events.iter().map(|(_, o)| o).collect();
+        assert_eq!(stats.gaps.values().sum::<usize>(), offsets.len() - 1);
+        let gap_total = stats
+            .gaps
+            .iter()
+            .map(|(&gap, &n)| gap * n as i32)
+            .fold(Duration::zero(), |a, b| a + b);
+        assert_eq!(gap_total, offsets[offsets.len() - 1] - offsets[0]);
+    }
+
+    #[test]
+    fn empty() {
+        let stats = test_util::with_events(&[]).data.stats();
+
+        assert_eq!(stats.num_events, 0);
+        assert_eq!(stats.total_duration, Duration::zero());
+        assert!(stats.gaps.is_empty());
+    }
+}
@@ -0,0 +1,92 @@
+//! Reading replays out of zip files (behind the `zip` feature).
+
+use std::io::{Read, Seek};
+
+use zip::{read::ZipFile, result::ZipError, ZipArchive};
+
+use crate::{decode_json, decode_uri_string, DecodeError, JstrisReplay};
+
+/// Decodes every file in a zip archive as a replay, yielding each along with
+/// the name of the file it came from.
+///
+/// Files can hold a URI encoded replay or a replay in JSON form (i.e. what
+/// [`decode_uri_string`] or [`decode_json`] accept). Directories are skipped.
+///
+/// Errors if `r` isn't a zip archive; errors reading or decoding individual
+/// files are yielded from the iterator.
+pub fn decode_archive<R: Read + Seek>(
+    r: R,
+) -> Result<impl Iterator<Item = Result<(String, JstrisReplay), DecodeError>>, DecodeError> {
+    let mut archive = ZipArchive::new(r)?;
+
+    Ok((0..archive.len()).filter_map(move |idx| {
+        let file = match archive.by_index(idx) {
+            Ok(file) => file,
+            Err(err) => return Some(Err(err.into())),
+        };
+        if file.is_dir() {
+            return None;
+        }
+
+        Some(decode_file(file))
+    }))
+}
+
+fn decode_file(mut file: ZipFile<'_, impl Read>) -> Result<(String, JstrisReplay), DecodeError> {
+    let name = file.name()?.into_owned();
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).map_err(ZipError::from)?;
+
+    let replay = if contents.trim_start().starts_with('{') {
+        decode_json(contents)
+    } else {
+        decode_uri_string(contents)
+    }?;
+
+    Ok((name, replay))
+}
+
+#[cfg(test)]
+mod test_zip_archive {
+    use std::io::{Cursor, Write};
+
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use super::*;
+    use crate::{encode_uri_string, test_util};
+
+    #[test]
+    fn two_replays() {
+        let sample = test_util::sample();
+
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        let options = SimpleFileOptions::default();
+        zip.start_file("a.txt", options).unwrap();
+        zip.write_all(encode_uri_string(&sample).unwrap().as_bytes())
+            .unwrap();
+        zip.add_directory("more/", options).unwrap();
+        zip.start_file("more/b.json", options).unwrap();
+        zip.write_all(test_util::SAMPLE_JSON.as_bytes()).unwrap();
+        let zip = zip.finish().unwrap();
+
+        let replays: Vec<_> = decode_archive(zip)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            replays,
+            [
+                ("a.txt".to_string(), sample.clone()),
+                ("more/b.json".to_string(), sample),
+            ]
+        );
+    }
+
+    #[test]
+    fn not_a_zip() {
+        assert!(matches!(
+            decode_archive(Cursor::new(b"not a zip")),
+            Err(DecodeError::ZipError(_))
+        ));
+    }
+}
@@ -0,0 +1,50 @@
+//! Runs the CLI binary against replays on disk.
+
+use std::{env, fs, path::PathBuf, process::Command};
+
+use chrono::Duration;
+use jstris_replay_re::{decode_uri_string, encode_uri_string, Input};
+
+fn corpus(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/corpus")
+        .join(name)
+}
+
+fn run(args: &[&str]) -> String {
+    let out = Command::new(env!("CARGO_BIN_EXE_jstris-replay-re"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{out:?}");
+
+    String::from_utf8(out.stdout).unwrap()
+}
+
+#[test]
+fn diff() {
+    let original = corpus("sample-40l.txt");
+    let mut replay = decode_uri_string(fs::read_to_string(&original).unwrap()).unwrap();
+    replay
+        .data
+        .insert_event(Input::Rotate180, Duration::milliseconds(1000))
+        .unwrap();
+
+    let edited = env::temp_dir().join(format!("jstris-replay-cli-diff-{}", std::process::id()));
+    fs::write(&edited, encode_uri_string(&replay).unwrap()).unwrap();
+
+    let out = run(&["diff", original.to_str().unwrap(), edited.to_str().unwrap()]);
+    fs::remove_file(&edited).unwrap();
+
+    assert!(out.contains("common prefix: 27 events"), "{out}");
+    assert!(out.contains("diverges at: PT1S"), "{out}");
+    assert!(out.contains("> @PT1.025S MoveRight"), "{out}");
+    assert!(out.contains("@PT1S Rotate180"), "{out}");
+
+    let out = run(&[
+        "diff",
+        original.to_str().unwrap(),
+        original.to_str().unwrap(),
+    ]);
+    assert!(out.contains("identical"), "{out}");
+}
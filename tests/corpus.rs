@@ -0,0 +1,36 @@
+//! Round trips every replay in `tests/corpus/` through the encoder.
+//!
+//! Each file holds a single URI encoded replay. `sample-*` files are real
+//! replays; `synthetic-*` files were derived from them (different modes,
+//! handling settings, lengths, etc.) to cover more of the format. The
+//! synthetic ones were encoded by this crate, so they can't catch a mismatch
+//! between our encoding and jstris's.
+
+use std::{fs, path::Path};
+
+use jstris_replay_re::{decode_uri_string, encode_uri_string};
+
+#[test]
+fn round_trip() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+
+    let mut count = 0;
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "txt") {
+            continue;
+        }
+
+        let name = path.display();
+        let encoded = fs::read_to_string(&path).unwrap();
+        let replay = decode_uri_string(&encoded).unwrap_or_else(|e| panic!("{name}: {e}"));
+
+        let reencoded = encode_uri_string(&replay).unwrap();
+        let redecoded = decode_uri_string(&reencoded).unwrap_or_else(|e| panic!("{name}: {e}"));
+
+        assert_eq!(redecoded, replay, "{name}");
+        count += 1;
+    }
+
+    assert!(count >= 6, "only found {count} replays in the corpus");
+}